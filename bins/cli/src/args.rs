@@ -1,5 +1,8 @@
-use clap::{Args, Parser, Subcommand};
+use crate::config::CONFIG;
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use once_cell::sync::Lazy;
+use std::path::PathBuf;
 use unipac_macros::{for_all, for_all_attrs};
 
 pub static ARGS: Lazy<UnipacArgs> = Lazy::new(parse);
@@ -10,6 +13,18 @@ pub struct UnipacArgs {
     #[arg(short, long)]
     pub no_interactive: bool,
 
+    /// Print machine-readable output instead of a table
+    #[arg(short, long, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Show what would be done without doing it
+    #[arg(short, long)]
+    pub dry_run: bool,
+
+    /// Never prompt, taking the default answer of every question
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
     #[command(flatten)]
     pub managers: Managers,
 
@@ -17,6 +32,13 @@ pub struct UnipacArgs {
     pub command: Option<Command>,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Ndjson,
+    Tsv,
+}
+
 #[derive(Subcommand)]
 pub enum Command {
     List {
@@ -26,23 +48,152 @@ pub enum Command {
     Search {
         query: String,
     },
-    Install {
+    /// Show everything the managers know about a package (`manager:package` allowed)
+    Info {
         query: String,
     },
+    /// Show which package a file, or a command found in `$PATH`, belongs to
+    Owns {
+        path: String,
+    },
+    /// Install packages, `manager:package` restricting the search to one manager
+    Install {
+        #[arg(required = true)]
+        queries: Vec<String>,
+        #[command(flatten)]
+        flatpak: FlatpakTarget,
+    },
+    /// Uninstall packages, `manager:package` restricting the search to one manager
     Uninstall {
-        query: String,
+        #[arg(required = true)]
+        queries: Vec<String>,
     },
+    /// Remove the packages nothing needs anymore
+    Autoremove,
+    /// Update every package, or only the given ones (`manager:package` allowed)
     Update {
-        query: Option<String>,
+        queries: Vec<String>,
         #[arg(short, long)]
         list: bool,
         #[arg(short, long)]
         count: bool,
+        /// Pick the updates to apply from the list
+        #[arg(long)]
+        select: bool,
+    },
+    /// Write the installed packages to a manifest (`-` for stdout)
+    Export {
+        #[arg(default_value = "unipac.toml")]
+        file: PathBuf,
+        /// Pin the installed versions
+        #[arg(long)]
+        pin: bool,
+    },
+    /// Install the packages of a manifest that are missing
+    Sync {
+        #[arg(default_value = "unipac.toml")]
+        file: PathBuf,
+        /// Also uninstall the packages the manifest does not list
+        #[arg(long)]
+        prune: bool,
     },
+    /// Show what unipac did, or the details of one transaction
+    History {
+        id: Option<u64>,
+        /// Only show the changes of packages matching this name
+        #[arg(long)]
+        package: Option<String>,
+        /// Only show transactions made on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Only show transactions made on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+    /// Hold a package back from updates, or list the pinned packages
+    Pin {
+        /// Package to pin, `manager:package` restricting it to one manager
+        query: Option<String>,
+        /// Version to hold the package at, the installed one by default
+        version: Option<String>,
+    },
+    /// Let a pinned package be updated again
+    Unpin {
+        query: String,
+    },
+    /// Revert a transaction, by default the last one that is not an undo nor undone already
+    Undo {
+        id: Option<u64>,
+    },
+    /// Show or clean what the managers keep in their caches
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+        /// Versions of each package to keep in the caches
+        #[arg(short, long, default_value_t = 3, global = true)]
+        keep: usize,
+    },
+    /// Look for problems with the installed packages
+    Doctor {
+        #[command(subcommand)]
+        check: DoctorCheck,
+    },
+    /// List, add or remove the remotes flatpak installs packages from
+    #[cfg(feature = "flatpak")]
+    Remote {
+        #[command(subcommand)]
+        action: RemoteAction,
+        /// Installation of the remotes: user, system, or a custom one
+        #[arg(long, global = true)]
+        installation: Option<String>,
+    },
+}
+
+/// Where `install` puts flatpak packages.
+#[derive(Args, Clone, Default)]
+pub struct FlatpakTarget {
+    /// Flatpak installation to install to: user, system, or a custom one
+    #[arg(long)]
+    pub installation: Option<String>,
+    /// Flatpak remote to install from
+    #[arg(long)]
+    pub remote: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum DoctorCheck {
+    /// Software installed by several managers, and which copy wins on `$PATH`
+    Duplicates,
+}
+
+#[cfg(feature = "flatpak")]
+#[derive(Subcommand)]
+pub enum RemoteAction {
+    /// Show the remotes of every installation
+    List,
+    /// Add a remote, from its repository URL or a `.flatpakrepo` file
+    Add { name: String, url: String },
+    /// Remove a remote
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// Show the space the caches take, and how much cleaning them would free
+    Stats,
+    /// Remove old package versions and leftover files from the caches
+    Clean,
+}
+
+impl UnipacArgs {
+    /// Whether questions can be asked to the user.
+    pub fn interactive(&self) -> bool {
+        !self.no_interactive && !self.yes
+    }
 }
 
 #[for_all_attrs]
-#[derive(Args, Default)]
+#[derive(Args, Clone, Default)]
 pub struct Managers {
     #[arg(short, long)]
     pub __manager: bool,
@@ -57,10 +208,45 @@ impl Managers {
         }
         false
     }
+
+    pub fn contains(&self, manager: &str) -> bool {
+        for_all! {
+            if manager == "__manager" {
+                return self.__manager;
+            }
+        }
+        false
+    }
+
+    /// Only `manager`, if it is a known one.
+    pub fn only(manager: &str) -> Option<Self> {
+        let mut managers = Self::default();
+        for_all! {
+            if manager == "__manager" {
+                managers.__manager = true;
+                return Some(managers);
+            }
+        }
+        None
+    }
+
+    /// Splits a `manager:package` target, falling back to `self` when the
+    /// target has no known manager prefix.
+    pub fn target<'a>(&self, target: &'a str) -> (Self, &'a str) {
+        target
+            .split_once(':')
+            .and_then(|(manager, name)| Some((Self::only(manager)?, name)))
+            .unwrap_or_else(|| (self.clone(), target))
+    }
 }
 
 fn parse() -> UnipacArgs {
     let mut args = UnipacArgs::parse();
+    if !args.managers.any() {
+        for_all! {
+            args.managers.__manager = CONFIG.is_default_manager("__manager");
+        }
+    }
     if !args.managers.any() {
         for_all! {
             args.managers.__manager = true;
@@ -1,16 +1,29 @@
-use crate::args::Managers;
+use crate::args::{FlatpakTarget, Managers};
+use crate::config::CONFIG;
+use crate::error::Error;
 use crate::hooks::*;
+use crate::output::{
+    self, CacheRecord, CopyRecord, CountRecord, DetailsRecord, DuplicateRecord, ErrorRecord,
+    InstalledVersions, PackageRecord, PlanRecord,
+};
 use crate::style::*;
 use crate::utils::sudo::elevate;
 use crate::utils::tabwriter::*;
 use crate::{args::ARGS, utils::spinners::Spinners};
-use dialoguer::{Confirm, Select};
-use std::fmt::Display;
+use chrono::NaiveDate;
+use dialoguer::{Confirm, MultiSelect, Select};
+use std::collections::{BTreeMap, HashSet};
 use std::io::Write as _;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tabwriter::TabWriter;
 use unipac_macros::{for_all, for_all_attrs};
-use unipac_managers::managers::{self, Manager};
+use unipac_managers::history::{self, Change, Transaction};
+use unipac_managers::managers::{
+    self, Action, CacheUsage, Exports, Manager, PackageDetails, PackageInfo, PlanItem,
+};
+use unipac_managers::manifest::{Diff, Entry, Manifest, Section};
+use unipac_managers::pins::{Pin, Pins};
 
 #[for_all_attrs]
 #[derive(Default)]
@@ -25,7 +38,7 @@ impl Packages {
         }
         total
     }
-    fn to_msg<T>(n: &Vec<T>) -> String {
+    fn to_msg<T>(n: &[T]) -> String {
         n.len().to_string()
     }
 }
@@ -51,6 +64,31 @@ impl Package {
     }
 }
 
+#[for_all_attrs]
+#[derive(Default)]
+struct Details {
+    pub __manager: Option<PackageDetails>,
+}
+impl Details {
+    fn to_msg(details: &Option<PackageDetails>) -> String {
+        details
+            .as_ref()
+            .map(|details| details.version.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[for_all_attrs]
+#[derive(Default)]
+struct Plans {
+    pub __manager: Vec<PlanItem>,
+}
+impl Plans {
+    fn to_msg(n: &[PlanItem]) -> String {
+        n.len().to_string()
+    }
+}
+
 #[for_all_attrs]
 #[derive(Default)]
 struct Counts {
@@ -70,28 +108,50 @@ impl Counts {
 }
 
 #[for_all_attrs]
-enum Error {
-    __Manager(managers::__manager::Error),
+#[derive(Default)]
+struct CacheUsages {
+    pub __manager: CacheUsage,
 }
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            #[cfg(feature = "pacman")]
-            Self::Pacman(err) => write!(f, "Pacman: {}", err),
-            #[cfg(feature = "aur")]
-            Self::AUR(err) => write!(f, "AUR: {}", err),
-            #[cfg(feature = "flatpak")]
-            Self::Flatpak(err) => write!(f, "Flatpak: {}", err),
-            #[cfg(feature = "snap")]
-            Self::Snap(err) => write!(f, "Snap: {}", err),
-            #[cfg(feature = "cargo")]
-            Self::Cargo(err) => write!(f, "Cargo: {}", err),
+impl CacheUsages {
+    fn total(&self) -> CacheUsage {
+        let mut total = CacheUsage::default();
+        for_all! {
+            total.size += self.__manager.size;
+            total.reclaimable += self.__manager.reclaimable;
         }
+        total
+    }
+    fn to_msg(usage: &CacheUsage) -> String {
+        format_size(usage.size)
+    }
+}
+
+#[for_all_attrs]
+#[derive(Default)]
+struct Sizes {
+    pub __manager: u64,
+}
+impl Sizes {
+    fn total(&self) -> u64 {
+        let mut total = 0;
+        for_all! {
+            total += self.__manager;
+        }
+        total
+    }
+    fn to_msg(size: &u64) -> String {
+        format_size(*size)
+    }
+}
+
+fn report_errors(errors: &[Error]) {
+    for error in errors {
+        eprintln!("{}", error);
     }
 }
 
 fn get_spinners(managers: &Managers) -> Option<Spinners> {
-    if !ARGS.no_interactive {
+    if !ARGS.no_interactive && ARGS.output.is_none() {
         Some(Spinners::with_managers(managers))
     } else {
         None
@@ -109,7 +169,8 @@ macro_rules! execute {
             let (__manager_handle, __manager_receiver) = if $managers.__manager {
                 let spinners = spinners.clone();
                 $(let $to_clone = $to_clone.clone();)*
-                let (progress_sender, progress_receiver) = tokio::sync::mpsc::channel(1);
+                // Room for the events sent from callbacks, which cannot wait
+                let (progress_sender, progress_receiver) = tokio::sync::mpsc::channel(64);
                 let handle = tokio::spawn(async move {
                     let manager = managers::__Manager::with_progress(progress_sender);
                     let packages_result = manager.$fn($($arg,)*).await;
@@ -134,19 +195,23 @@ macro_rules! execute {
                 if let Some(mut receiver) = __manager_receiver {
                     local.spawn_local(async move {
                         while let Some(progress) = receiver.recv().await {
-                            spinners_clone.__manager_set_message(progress);
+                            spinners_clone.__manager_set_progress(progress);
                         }
                     });
                 }
             }
             tokio::join!(local, spinners.work());
-
+        } else {
+            // Nobody listens, so that senders do not wait on a full channel
             for_all! {
-                if let Some(handle) = __manager_handle {
-                    let result = handle.await.unwrap();
-                    if let Err(err) = result {
-                        errors.push(Error::__Manager(err));
-                    }
+                drop(__manager_receiver);
+            }
+        }
+        for_all! {
+            if let Some(handle) = __manager_handle {
+                let result = handle.await.unwrap();
+                if let Err(err) = result {
+                    errors.push(Error::__Manager(err));
                 }
             }
         }
@@ -155,10 +220,13 @@ macro_rules! execute {
 }
 
 macro_rules! get_results {
-    ([$($to_clone:expr,)*], $fn:ident, $result:ident, [$($arg:expr,)*]) => {{
-        let spinners = get_spinners(&ARGS.managers);
+    ([$($to_clone:expr,)*], $fn:ident, $result:ident, [$($arg:expr,)*]) => {
+        get_results!([$($to_clone,)*], $fn, $result, [$($arg,)*], ARGS.managers)
+    };
+    ([$($to_clone:expr,)*], $fn:ident, $result:ident, [$($arg:expr,)*], $managers:expr) => {{
+        let spinners = get_spinners(&$managers);
         for_all! {
-            let __manager_handle = if ARGS.managers.__manager {
+            let __manager_handle = if $managers.__manager {
                 let spinners = spinners.clone();
                 $(let $to_clone = $to_clone.clone();)*
                 let result = tokio::spawn(async move {
@@ -182,6 +250,7 @@ macro_rules! get_results {
             spinners.work().await;
         }
         let mut results = $result::default();
+        let mut errors = Vec::new();
         for_all! {
             if let Some(handle) = __manager_handle {
                 match handle.await.unwrap() {
@@ -189,12 +258,12 @@ macro_rules! get_results {
                         results.__manager = result;
                     }
                     Err(err) => {
-                        eprintln!("__Manager: {}", err);
+                        errors.push(Error::__Manager(err));
                     }
                 }
             }
         }
-        results
+        (results, errors)
     }};
 }
 
@@ -205,7 +274,7 @@ fn print_packages(packages: &Packages) {
         if !packages.__manager.is_empty() {
             let str = packages.__manager
                 .iter()
-                .map(__manager_to_string)
+                .map(|package| package_to_string(&__MANAGER_STYLE, "__Manager", package))
                 .fold(String::new(), |acc, s| acc + &s);
             output.push_str(&format!("{}\n", str));
         }
@@ -215,184 +284,1641 @@ fn print_packages(packages: &Packages) {
     tw.flush().expect("failed to flush output");
 }
 
-pub async fn list() {
-    let packages = get_results!([], list, Packages, []);
+fn package_records(packages: &Packages) -> Vec<PackageRecord> {
+    let mut records = Vec::with_capacity(packages.total());
+    for_all! {
+        records.extend(
+            packages
+                .__manager
+                .iter()
+                .map(|package| PackageRecord::new("__manager", package)),
+        );
+    }
+    records
+}
+
+fn update_records(packages: &Packages, installed: &InstalledVersions) -> Vec<PackageRecord> {
+    let mut records = Vec::with_capacity(packages.total());
+    for_all! {
+        records.extend(packages.__manager.iter().map(|package| {
+            let version = installed.get(&("__manager", package.name().to_string()));
+            PackageRecord::update("__manager", package, version)
+        }));
+    }
+    records
+}
+
+fn error_records(errors: &[Error]) -> Vec<ErrorRecord> {
+    errors.iter().map(Error::to_record).collect()
+}
+
+/// Installed versions of the enabled managers, a failing list not being reported.
+async fn installed_versions() -> InstalledVersions {
+    let (installed, _): (_, Vec<Error>) = get_results!([], list, Packages, []);
+    package_records(&installed)
+        .into_iter()
+        .filter_map(|record| Some(((record.manager, record.name), record.version?)))
+        .collect()
+}
+
+/// Asks `prompt`, answering yes when prompts are disabled.
+fn confirm(prompt: &str) -> bool {
+    !CONFIG.prompts.confirm
+        || !ARGS.interactive()
+        || Confirm::new()
+            .with_prompt(prompt)
+            .default(true)
+            .interact()
+            .expect("Failed to read input")
+}
+
+fn record_history(command: &str, changes: Vec<Change>) {
+    if changes.is_empty() {
+        return;
+    }
+    if let Err(err) = history::record(command, changes) {
+        eprintln!("Failed to record history: {}", err);
+    }
+}
+
+fn manager_label(manager: &str) -> String {
+    for_all! {
+        if manager == "__manager" {
+            return __MANAGER_STYLE.apply_to("__Manager:").to_string();
+        }
+    }
+    format!("{}:", manager)
+}
+
+fn print_plans(plans: &Plans, errors: &[Error]) {
+    if let Some(format) = ARGS.output {
+        let mut records = Vec::new();
+        for_all! {
+            records.extend(
+                plans
+                    .__manager
+                    .iter()
+                    .map(|item| PlanRecord::new("__manager", item)),
+            );
+        }
+        output::print_plan(format, &records, &error_records(errors));
+        return;
+    }
+    report_errors(errors);
+    println!("\n");
+    let mut tw = TabWriter::new(std::io::stdout());
+    let mut output = String::new();
+    let mut download_size = 0;
+    for_all! {
+        for item in &plans.__manager {
+            output.push_str(&plan_item_to_string(&__MANAGER_STYLE, "__Manager", item));
+            download_size += item.download_size.unwrap_or_default();
+        }
+    }
+    if output.is_empty() {
+        println!("Nothing to do.");
+        return;
+    }
+    write!(&mut tw, "{}", output).expect("failed to write output");
+    tw.flush().expect("failed to flush output");
+    if download_size > 0 {
+        println!("\nTotal download size: {}", format_size(download_size));
+    }
+}
+
+pub async fn list() -> Vec<Error> {
+    let (packages, errors) = get_results!([], list, Packages, []);
+    if let Some(format) = ARGS.output {
+        output::print_packages(format, &package_records(&packages), &error_records(&errors));
+        return errors;
+    }
+    report_errors(&errors);
     print_packages(&packages);
+    errors
 }
 
-pub async fn search(query: &str) {
+pub async fn search(query: &str) -> Vec<Error> {
     let query: Arc<str> = Arc::from(query);
-    let packages = get_results!([query,], search, Packages, [(&query),]);
+    let (packages, errors) = get_results!([query,], search, Packages, [(&query),]);
+    if let Some(format) = ARGS.output {
+        output::print_packages(format, &package_records(&packages), &error_records(&errors));
+        return errors;
+    }
+    report_errors(&errors);
     print_packages(&packages);
+    errors
 }
 
-pub async fn list_updates() {
-    let packages = get_results!([], list_updates, Packages, []);
+pub async fn info(target: &str) -> Vec<Error> {
+    let (managers, query) = ARGS.managers.target(target);
+    let query: Arc<str> = Arc::from(query);
+    let (details, errors) = get_results!([query,], info, Details, [&query,], managers);
+    let mut found = Vec::new();
+    for_all! {
+        if let Some(details) = details.__manager {
+            found.push(("__manager", details));
+        }
+    }
+    if let Some(format) = ARGS.output {
+        let records = found
+            .iter()
+            .map(|(manager, details)| DetailsRecord { manager, details })
+            .collect::<Vec<_>>();
+        output::print_details(format, &records, &error_records(&errors));
+        return errors;
+    }
+    report_errors(&errors);
+    if found.is_empty() {
+        println!("{} was not found.", query);
+        return errors;
+    }
+    for (manager, details) in &found {
+        let state = if details.installed {
+            " (installed)"
+        } else {
+            ""
+        };
+        println!(
+            "\n{} {} {}{}",
+            manager_label(manager),
+            details.name,
+            details.version,
+            state,
+        );
+        let mut tw = TabWriter::new(std::io::stdout());
+        write!(&mut tw, "{}", details_to_string(details)).expect("failed to write output");
+        tw.flush().expect("failed to flush output");
+    }
+    errors
+}
+
+/// Absolute path of `target`, looked up in `$PATH` when it is a bare command.
+fn resolve_path(target: &str) -> Option<PathBuf> {
+    if target.contains('/') {
+        return std::env::current_dir().ok().map(|dir| dir.join(target));
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(target))
+        .find(|path| path.is_file())
+}
+
+pub async fn owns(target: &str) -> Vec<Error> {
+    let Some(path) = resolve_path(target) else {
+        println!("{} was not found in $PATH.", target);
+        return vec![];
+    };
+    // Links like `/usr/bin/java` are not always owned by the package of
+    // their target, so the link itself is looked up first
+    let mut paths = vec![path.clone()];
+    if let Ok(canonical) = path.canonicalize() {
+        if canonical != path {
+            paths.push(canonical);
+        }
+    }
+    let mut errors = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let path: Arc<Path> = Arc::from(path.as_path());
+        let (owners, path_errors) = get_results!([path,], owns, Package, [&path,]);
+        errors.extend(path_errors);
+        let mut output = String::new();
+        let mut records = Vec::new();
+        for_all! {
+            if let Some(package) = &owners.__manager {
+                output.push_str(&package_to_string(&__MANAGER_STYLE, "__Manager", package));
+                records.push(PackageRecord::new("__manager", package));
+            }
+        }
+        if records.is_empty() && i + 1 < paths.len() {
+            continue;
+        }
+        if let Some(format) = ARGS.output {
+            output::print_packages(format, &records, &error_records(&errors));
+            return errors;
+        }
+        report_errors(&errors);
+        if records.is_empty() {
+            println!("{} is not owned by any package.", path.display());
+            return errors;
+        }
+        println!("{} is owned by:", path.display());
+        let mut tw = TabWriter::new(std::io::stdout());
+        write!(&mut tw, "{}", output).expect("failed to write output");
+        tw.flush().expect("failed to flush output");
+        return errors;
+    }
+    errors
+}
+
+/// Name the same software is known by across managers: `org.mozilla.firefox`,
+/// `Firefox` and `firefox-bin` all become `firefox`.
+fn normalize_name(name: &str) -> String {
+    let name = name.to_lowercase();
+    // Reverse-DNS IDs of flatpak applications and desktop entries
+    let name = if name.matches('.').count() >= 2 {
+        name.rsplit('.').next().unwrap_or_default()
+    } else {
+        &name
+    };
+    let name = name.replace('_', "-");
+    ["-bin", "-git"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .map_or_else(|| name.clone(), String::from)
+}
+
+/// Whether running the name of `bin` from `$PATH` executes `bin`.
+fn wins_on_path(bin: &Path) -> bool {
+    let Some(found) = bin
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(resolve_path)
+    else {
+        return false;
+    };
+    found == bin || matches!((found.canonicalize(), bin.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// One of the installed copies of a software.
+struct InstalledCopy {
+    manager: &'static str,
+    name: String,
+    version: String,
+    names: Vec<String>,
+    exports: Exports,
+}
+
+pub async fn duplicates() -> Vec<Error> {
+    let (packages, mut errors) = get_results!([], list, Packages, []);
+    let mut copies = Vec::with_capacity(packages.total());
+    for_all! {
+        if !packages.__manager.is_empty() {
+            let manager = managers::__Manager::new();
+            for package in &packages.__manager {
+                let exports = match manager.exports(package).await {
+                    Ok(exports) => exports,
+                    Err(err) => {
+                        errors.push(Error::__Manager(err));
+                        Exports::default()
+                    }
+                };
+                let mut names = vec![normalize_name(package.name())];
+                names.extend(
+                    exports
+                        .bins
+                        .iter()
+                        .filter_map(|bin| bin.file_name()?.to_str())
+                        .chain(exports.desktop_entries.iter().map(String::as_str))
+                        .map(normalize_name),
+                );
+                names.sort();
+                names.dedup();
+                copies.push(InstalledCopy {
+                    manager: "__manager",
+                    name: package.name().into(),
+                    version: package.version().into(),
+                    names,
+                    exports,
+                });
+            }
+        }
+    }
+
+    let mut by_name: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, copy) in copies.iter().enumerate() {
+        for name in &copy.names {
+            by_name.entry(name).or_default().push(i);
+        }
+    }
+    // Copies sharing several names are reported once
+    let mut groups: BTreeMap<Vec<usize>, Vec<String>> = BTreeMap::new();
+    for (name, indices) in by_name {
+        let managers = indices
+            .iter()
+            .map(|&i| copies[i].manager)
+            .collect::<HashSet<_>>();
+        if managers.len() > 1 {
+            groups.entry(indices).or_default().push(name.into());
+        }
+    }
+    let records = groups
+        .into_iter()
+        .map(|(indices, names)| DuplicateRecord {
+            names,
+            copies: indices
+                .into_iter()
+                .map(|i| {
+                    let copy = &copies[i];
+                    CopyRecord {
+                        manager: copy.manager,
+                        name: copy.name.clone(),
+                        version: copy.version.clone(),
+                        wins: copy
+                            .exports
+                            .bins
+                            .iter()
+                            .filter(|bin| wins_on_path(bin))
+                            .filter_map(|bin| Some(bin.file_name()?.to_str()?.to_string()))
+                            .collect(),
+                    }
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(format) = ARGS.output {
+        output::print_duplicates(format, &records, &error_records(&errors));
+        return errors;
+    }
+    report_errors(&errors);
+    if records.is_empty() {
+        println!("No software is installed by several managers.");
+        return errors;
+    }
+    let mut output = String::new();
+    for record in &records {
+        output.push_str(&format!("{}:\n", record.names.join(", ")));
+        for copy in &record.copies {
+            let wins = if copy.wins.is_empty() {
+                String::new()
+            } else {
+                format!("runs as {}", copy.wins.join(", "))
+            };
+            output.push_str(&format!(
+                "  {}\t{}\t{}\t{}\n",
+                manager_label(copy.manager),
+                copy.name,
+                copy.version,
+                wins,
+            ));
+        }
+    }
+    let mut tw = TabWriter::new(std::io::stdout());
+    write!(&mut tw, "{}", output).expect("failed to write output");
+    tw.flush().expect("failed to flush output");
+    errors
+}
+
+pub async fn list_updates() -> Vec<Error> {
+    let (packages, errors) = get_results!([], list_updates, Packages, []);
+    if let Some(format) = ARGS.output {
+        let installed = installed_versions().await;
+        let records = update_records(&packages, &installed);
+        output::print_packages(format, &records, &error_records(&errors));
+        return errors;
+    }
+    report_errors(&errors);
     if packages.total() == 0 {
-        println!("Aucune mise à jour disponible.");
-        return;
+        println!("No updates available.");
+        return errors;
     }
     print_packages(&packages);
+    errors
 }
 
-pub async fn install(query: &str) {
-    elevate();
-    let query: Arc<str> = Arc::from(query);
-    let packages = get_results!([query,], search_install, Packages, [&query,]);
-    println!("\n");
-    if packages.total() == 0 {
-        println!("Aucun paquet trouvé.");
-        return;
+#[cfg_attr(not(feature = "flatpak"), allow(unused_variables))]
+pub async fn install(targets: &[String], flatpak: &FlatpakTarget) -> Vec<Error> {
+    if !ARGS.dry_run {
+        elevate();
     }
-    let mut options: Vec<String> = Vec::with_capacity(packages.total());
-    #[cfg(feature = "pacman")]
-    for package in &packages.pacman {
-        options.push(format!(
-            "{}: {} {} {}",
-            PACMAN_STYLE.apply_to("Pacman"),
-            package.database,
-            package.name,
-            package.version,
-        ));
+    let mut selected = Packages::default();
+    let mut errors = Vec::new();
+    for target in targets {
+        let (managers, query) = ARGS.managers.target(target);
+        let query: Arc<str> = Arc::from(query);
+        let (mut packages, search_errors) =
+            get_results!([query,], search_install, Packages, [&query,], managers);
+        report_errors(&search_errors);
+        errors.extend(search_errors);
+        println!("\n");
+        // (manager, index in its packages, exact name match, label)
+        let mut candidates: Vec<(&str, usize, bool, String)> = Vec::with_capacity(packages.total());
+        for_all! {
+            for (index, package) in packages.__manager.iter().enumerate() {
+                candidates.push((
+                    "__manager",
+                    index,
+                    package.name() == &*query
+                        || package.identifier().is_some_and(|id| id == *query),
+                    format!(
+                        "{}: {} {}{}",
+                        __MANAGER_STYLE.apply_to("__Manager"),
+                        package.name(),
+                        package.version(),
+                        package
+                            .origin()
+                            .map(|origin| format!(" ({})", origin))
+                            .unwrap_or_default(),
+                    ),
+                ));
+            }
+        }
+        if candidates.is_empty() {
+            println!("No packages found for {}.", query);
+            continue;
+        }
+        // Exact matches first, then by configured priority
+        candidates.sort_by_key(|(manager, _, exact, _)| (!exact, CONFIG.priority(manager)));
+        let selection = if ARGS.interactive() {
+            let options = candidates
+                .iter()
+                .map(|(.., label)| label)
+                .collect::<Vec<_>>();
+            Select::new()
+                .with_prompt(format!(
+                    "Which package do you want to install for {}?",
+                    query
+                ))
+                .items(&options)
+                .default(0)
+                .interact()
+                .expect("Failed to read input")
+        } else {
+            println!("Selected {}", candidates[0].3);
+            0
+        };
+
+        let (selected_manager, index, ..) = candidates[selection];
+        for_all! {
+            if selected_manager == "__manager" {
+                let package = packages.__manager.swap_remove(index);
+                if !selected.__manager.iter().any(|p| p.name() == package.name()) {
+                    selected.__manager.push(package);
+                }
+            }
+        }
     }
-    #[cfg(feature = "aur")]
-    for package in &packages.aur {
-        options.push(format!(
-            "{}: {} {}",
-            AUR_STYLE.apply_to("AUR"),
-            package.name,
-            package.version,
-        ))
+    if selected.total() == 0 {
+        return errors;
     }
     #[cfg(feature = "flatpak")]
-    for package in &packages.flatpak {
-        options.push(format!(
-            "{}: {} {}",
-            FLATPAK_STYLE.apply_to("Flatpak"),
-            package.name,
-            package.version,
-        ))
+    for package in &mut selected.flatpak {
+        if let Some(installation) = &flatpak.installation {
+            package.installation = Some(installation.clone().into());
+        }
+        if let Some(remote) = &flatpak.remote {
+            package.origin = Some(remote.clone());
+        }
     }
-    #[cfg(feature = "snap")]
-    for package in &packages.snap {
-        options.push(format!(
-            "{}: {} {}",
-            SNAP_STYLE.apply_to("Snap"),
-            package.name,
-            package.version,
-        ))
+
+    if ARGS.dry_run {
+        let mut plans = Plans::default();
+        for_all! {
+            if !selected.__manager.is_empty() {
+                let manager = managers::__Manager::new();
+                for package in &selected.__manager {
+                    match manager.plan_install(package).await {
+                        Ok(plan) => plans.__manager.extend(plan),
+                        Err(err) => errors.push(Error::__Manager(err)),
+                    }
+                }
+            }
+        }
+        print_plans(&plans, &errors);
+        return errors;
     }
-    #[cfg(feature = "cargo")]
-    for package in &packages.cargo {
-        options.push(format!(
-            "{}: {} {}",
-            CARGO_STYLE.apply_to("Cargo"),
-            package.name,
-            package.version,
-        ))
+    if selected.total() > 1 {
+        print_packages(&selected);
+        if !confirm("Do you want to install these packages?") {
+            return errors;
+        }
     }
-    if options.is_empty() {
-        println!("No packages found.");
-        return;
+
+    let mut changes = Vec::with_capacity(selected.total());
+    for_all! {
+        if !selected.__manager.is_empty() {
+            for package in &selected.__manager {
+                __manager_pre_install(package).await;
+            }
+            let packages = selected.__manager.iter().collect::<Vec<_>>();
+            let result = managers::__Manager::new().install_many(&packages).await;
+            let error = result.as_ref().err().map(ToString::to_string);
+            changes.extend(packages.iter().map(|package| {
+                Change::new("__manager", PlanItem::install(*package)).with_error(error.clone())
+            }));
+            if let Err(err) = result {
+                errors.push(Error::__Manager(err));
+            }
+        }
     }
-    let selection = Select::new()
-        .with_prompt("Which package do you want to install?")
-        .items(&options)
-        .default(0)
-        .interact()
-        .expect("Failed to read input");
+    println!();
+    print_changes(&changes);
+    record_history("install", changes);
+    errors
+}
 
-    let mut i = 0;
+pub async fn uninstall(targets: &[String]) -> Vec<Error> {
+    if !ARGS.dry_run {
+        elevate();
+    }
+    let mut selected = Packages::default();
+    let mut errors = Vec::new();
+    for target in targets {
+        let (managers, query) = ARGS.managers.target(target);
+        let query: Arc<str> = Arc::from(query);
+        let (mut packages, find_errors) =
+            get_results!([query,], find, Package, [&query,], managers);
+        report_errors(&find_errors);
+        errors.extend(find_errors);
+        let mut owners = Vec::new();
+        for_all! {
+            if packages.__manager.is_some() {
+                owners.push("__manager");
+            }
+        }
+        owners.sort_by_key(|manager| CONFIG.priority(manager));
+        let Some(&selected_manager) = owners.first() else {
+            println!("{} is not installed.", query);
+            continue;
+        };
+        for_all! {
+            let package = packages
+                .__manager
+                .take()
+                .filter(|_| selected_manager == "__manager");
+            if let Some(package) = package {
+                if !selected.__manager.iter().any(|p| p.name() == package.name()) {
+                    selected.__manager.push(package);
+                }
+            }
+        }
+    }
+    if selected.total() == 0 {
+        return errors;
+    }
+
+    if ARGS.dry_run {
+        let mut plans = Plans::default();
+        for_all! {
+            if !selected.__manager.is_empty() {
+                let manager = managers::__Manager::new();
+                for package in &selected.__manager {
+                    match manager.plan_uninstall(package).await {
+                        Ok(plan) => plans.__manager.extend(plan),
+                        Err(err) => errors.push(Error::__Manager(err)),
+                    }
+                }
+            }
+        }
+        print_plans(&plans, &errors);
+        return errors;
+    }
+    print_packages(&selected);
+    let prompt = if selected.total() > 1 {
+        "Do you want to uninstall these packages?"
+    } else {
+        "Do you want to uninstall this package?"
+    };
+    if !confirm(prompt) {
+        return errors;
+    }
+
+    let mut changes = Vec::with_capacity(selected.total());
     for_all! {
-        let len = packages.__manager.len();
-        if len == 0 || selection - i > len {
-            i += len;
-        } else {
-            let package = &packages.__manager[selection - i];
-            __manager_pre_install(&package).await;
-            let manager = managers::__Manager::new();
-            let result = manager.install(package).await;
+        if !selected.__manager.is_empty() {
+            for package in &selected.__manager {
+                __manager_pre_uninstall(package).await;
+            }
+            let packages = selected.__manager.iter().collect::<Vec<_>>();
+            let result = managers::__Manager::new().uninstall_many(&packages).await;
+            let error = result.as_ref().err().map(ToString::to_string);
+            changes.extend(packages.iter().map(|package| {
+                Change::new("__manager", PlanItem::remove(*package)).with_error(error.clone())
+            }));
             if let Err(err) = result {
-                eprintln!("Failed to install {}: {}", package.name, err);
+                errors.push(Error::__Manager(err));
             }
-            return;
         }
     }
-    // Just for the linter
-    if i == 0 {}
-    println!("No packages found.");
+    println!();
+    print_changes(&changes);
+    record_history("uninstall", changes);
+    errors
 }
 
-pub async fn uninstall(query: &str) {
-    elevate();
-    let query: Arc<str> = Arc::from(query);
-    let packages = get_results!([query,], find, Package, [&query,]);
+pub async fn autoremove() -> Vec<Error> {
+    if !ARGS.dry_run {
+        elevate();
+    }
+    let (orphans, mut errors) = get_results!([], list_orphans, Packages, []);
+    if let Some(format) = ARGS.output {
+        output::print_packages(format, &package_records(&orphans), &error_records(&errors));
+    } else {
+        report_errors(&errors);
+        if orphans.total() == 0 {
+            println!("No orphans to remove.");
+            return errors;
+        }
+        let mut output = String::new();
+        let mut reclaimable = 0;
+        for_all! {
+            for package in &orphans.__manager {
+                let size = package.installed_size();
+                reclaimable += size.unwrap_or_default();
+                output.push_str(&format!(
+                    "{}\t{}\t{}\t{}\n",
+                    __MANAGER_STYLE.apply_to("__Manager:"),
+                    package.name(),
+                    package.version(),
+                    size.map(format_size).unwrap_or_default(),
+                ));
+            }
+        }
+        println!("\n");
+        let mut tw = TabWriter::new(std::io::stdout());
+        write!(&mut tw, "{}", output).expect("failed to write output");
+        tw.flush().expect("failed to flush output");
+        println!("\nReclaimable size: {}", format_size(reclaimable));
+    }
+    if ARGS.dry_run || orphans.total() == 0 || !confirm("Do you want to remove these packages?") {
+        return errors;
+    }
+
+    let mut changes = Vec::with_capacity(orphans.total());
     for_all! {
-        if let Some(package) = &packages.__manager {
-            println!("{}", __manager_to_string(package));
-            let might_uninstall = Confirm::new()
-                .with_prompt("Do you want to uninstall this package?")
-                .default(true)
-                .interact();
-            let might_uninstall = match might_uninstall {
-                Ok(might_uninstall) => might_uninstall,
-                Err(err) => {
-                    eprintln!("Failed to read input: {}", err);
-                    std::process::exit(1);
-                }
-            };
-            if !might_uninstall {
-                std::process::exit(0);
+        if !orphans.__manager.is_empty() {
+            for package in &orphans.__manager {
+                __manager_pre_uninstall(package).await;
             }
-            __manager_pre_uninstall(package).await;
-            let manager = managers::__Manager::new();
-            let result = manager.uninstall(package).await;
+            let packages = orphans.__manager.iter().collect::<Vec<_>>();
+            let result = managers::__Manager::new().remove_orphans(&packages).await;
+            let error = result.as_ref().err().map(ToString::to_string);
+            changes.extend(packages.iter().map(|package| {
+                Change::new("__manager", PlanItem::remove(*package)).with_error(error.clone())
+            }));
             if let Err(err) = result {
-                eprintln!("Failed to uninstall {}: {}", package.name, err);
+                errors.push(Error::__Manager(err));
             }
-            return;
         }
     }
-    println!("No packages found.");
+    println!();
+    print_changes(&changes);
+    record_history("autoremove", changes);
+    errors
+}
+
+/// Caches of the enabled managers, those that could not be measured left out.
+fn cache_records(
+    usages: &CacheUsages,
+    freed: Option<&Sizes>,
+    errors: &[Error],
+) -> Vec<CacheRecord> {
+    let mut records = Vec::new();
+    for_all! {
+        if ARGS.managers.__manager && !errors.iter().any(|e| e.manager() == "__manager") {
+            records.push(CacheRecord {
+                manager: "__manager",
+                usage: usages.__manager,
+                freed: freed.map(|freed| freed.__manager),
+            });
+        }
+    }
+    records
+}
+
+fn print_cache_usages(usages: &CacheUsages, errors: &[Error]) {
+    let mut output = String::new();
+    for_all! {
+        if ARGS.managers.__manager && !errors.iter().any(|e| e.manager() == "__manager") {
+            output.push_str(&format!(
+                "{}\t{}\t{} reclaimable\n",
+                __MANAGER_STYLE.apply_to("__Manager:"),
+                format_size(usages.__manager.size),
+                format_size(usages.__manager.reclaimable),
+            ));
+        }
+    }
+    let total = usages.total();
+    output.push_str(&format!(
+        "Total:\t{}\t{} reclaimable\n",
+        format_size(total.size),
+        format_size(total.reclaimable),
+    ));
+    println!();
+    let mut tw = TabWriter::new(std::io::stdout());
+    write!(&mut tw, "{}", output).expect("failed to write output");
+    tw.flush().expect("failed to flush output");
+}
+
+pub async fn cache_stats(keep: usize) -> Vec<Error> {
+    let (usages, errors) = get_results!([], cache_usage, CacheUsages, [keep,]);
+    let records = cache_records(&usages, None, &errors);
+    if let Some(format) = ARGS.output {
+        output::print_caches(format, &records, &error_records(&errors));
+        return errors;
+    }
+    report_errors(&errors);
+    print_cache_usages(&usages, &errors);
+    errors
 }
 
-pub async fn count_updates() {
-    let counts = get_results!([], count_updates, Counts, []);
+pub async fn clean_cache(keep: usize) -> Vec<Error> {
+    if !ARGS.dry_run {
+        elevate();
+    }
+    let (usages, mut errors) = get_results!([], cache_usage, CacheUsages, [keep,]);
+    let reclaimable = usages.total().reclaimable;
+    if ARGS.output.is_none() {
+        report_errors(&errors);
+        print_cache_usages(&usages, &errors);
+        if reclaimable == 0 {
+            println!("\nNothing to clean.");
+        }
+    }
+    if ARGS.dry_run
+        || reclaimable == 0
+        || !confirm(&format!(
+            "Do you want to free {}?",
+            format_size(reclaimable)
+        ))
+    {
+        if let Some(format) = ARGS.output {
+            let records = cache_records(&usages, None, &errors);
+            output::print_caches(format, &records, &error_records(&errors));
+        }
+        return errors;
+    }
+
+    // Caches that could not be measured are left alone
+    let mut managers = ARGS.managers.clone();
+    for_all! {
+        managers.__manager &= usages.__manager.reclaimable > 0;
+    }
+    let (freed, clean_errors) = get_results!([], clean_cache, Sizes, [keep,], managers);
+    errors.extend(clean_errors);
+    if let Some(format) = ARGS.output {
+        let records = cache_records(&usages, Some(&freed), &errors);
+        output::print_caches(format, &records, &error_records(&errors));
+        return errors;
+    }
+    report_errors(&errors);
+    println!();
+    for_all! {
+        if managers.__manager && !errors.iter().any(|e| e.manager() == "__manager") {
+            println!(
+                "{} freed {}",
+                __MANAGER_STYLE.apply_to("__Manager:"),
+                format_size(freed.__manager)
+            );
+        }
+    }
+    println!("\nFreed {} in total", format_size(freed.total()));
+    errors
+}
+
+pub async fn count_updates() -> Vec<Error> {
+    let (counts, errors) = get_results!([], count_updates, Counts, []);
+    if let Some(format) = ARGS.output {
+        let error_records = error_records(&errors);
+        let mut records = Vec::new();
+        for_all! {
+            if ARGS.managers.__manager && !errors.iter().any(|e| e.manager() == "__manager") {
+                records.push(CountRecord {
+                    manager: "__manager",
+                    count: counts.__manager,
+                });
+            }
+        }
+        output::print_counts(format, &records, &error_records);
+        return errors;
+    }
+    report_errors(&errors);
     if ARGS.no_interactive {
         println!("{}", counts.total());
     } else {
-        println!("Vous avez {} mises à jour.", counts.total());
+        println!("You have {} updates.", counts.total());
+    }
+    errors
+}
+
+/// Whether `name` of `manager` is one of the `targets` of a command.
+fn is_target(targets: &[(Managers, &str)], manager: &str, name: &str) -> bool {
+    targets
+        .iter()
+        .any(|(managers, target)| managers.contains(manager) && *target == name)
+}
+
+/// Keeps the updates the user picks.
+fn select_updates(packages: &mut Packages) {
+    let mut candidates = Vec::with_capacity(packages.total());
+    for_all! {
+        for (index, package) in packages.__manager.iter().enumerate() {
+            candidates.push((
+                "__manager",
+                index,
+                format!(
+                    "{}: {} {}",
+                    __MANAGER_STYLE.apply_to("__Manager"),
+                    package.name(),
+                    package.version(),
+                ),
+            ));
+        }
+    }
+    let labels = candidates
+        .iter()
+        .map(|(.., label)| label)
+        .collect::<Vec<_>>();
+    let chosen = MultiSelect::new()
+        .with_prompt("Which updates do you want to apply?")
+        .items(&labels)
+        .defaults(&vec![true; labels.len()])
+        .interact()
+        .expect("Failed to read input");
+    let chosen = chosen
+        .into_iter()
+        .map(|i| (candidates[i].0, candidates[i].1))
+        .collect::<Vec<_>>();
+    for_all! {
+        let mut index = 0;
+        packages.__manager.retain(|_| {
+            index += 1;
+            chosen.contains(&("__manager", index - 1))
+        });
     }
 }
 
-pub async fn update(_query: Option<&str>) {
+pub async fn update(queries: &[String], select: bool) -> Vec<Error> {
+    let targets = queries
+        .iter()
+        .map(|query| ARGS.managers.target(query))
+        .collect::<Vec<_>>();
+    let selective = !targets.is_empty() || select;
+    if ARGS.dry_run {
+        let (mut plans, errors) = get_results!([], plan_update, Plans, []);
+        if !targets.is_empty() {
+            for_all! {
+                plans
+                    .__manager
+                    .retain(|item| is_target(&targets, "__manager", &item.name));
+            }
+        }
+        print_plans(&plans, &errors);
+        return errors;
+    }
     elevate();
-    let packages = get_results!([], list_updates, Packages, []);
+    let (mut packages, mut errors) = get_results!([], list_updates, Packages, []);
+    report_errors(&errors);
+    if !targets.is_empty() {
+        for_all! {
+            packages
+                .__manager
+                .retain(|package| is_target(&targets, "__manager", package.name()));
+        }
+        for (managers, target) in &targets {
+            let mut found = false;
+            for_all! {
+                found |= managers.__manager
+                    && packages.__manager.iter().any(|p| p.name() == *target);
+            }
+            if !found {
+                println!("No update available for {}.", target);
+            }
+        }
+    }
     if packages.total() == 0 {
-        println!("Aucune mise à jour disponible.");
-        return;
+        println!("No updates available.");
+        return errors;
+    }
+    if select && ARGS.interactive() {
+        select_updates(&mut packages);
+        if packages.total() == 0 {
+            return errors;
+        }
+    } else {
+        print_packages(&packages);
     }
-    print_packages(&packages);
     for_all! {
         __manager_pre_update(&packages.__manager).await;
     }
-    let might_install = Confirm::new()
-        .with_prompt("Do you want to install these packages?")
-        .default(true)
-        .interact()
-        .expect("Failed to read input");
+    if !select && !confirm("Do you want to install these packages?") {
+        return errors;
+    }
 
-    if !might_install {
-        return;
+    let installed = installed_versions().await;
+    let update_errors = if selective {
+        let mut update_errors = Vec::new();
+        for_all! {
+            if !packages.__manager.is_empty() {
+                let updates = packages.__manager.iter().collect::<Vec<_>>();
+                let result = managers::__Manager::new().update_packages(&updates).await;
+                if let Err(err) = result {
+                    update_errors.push(Error::__Manager(err));
+                }
+            }
+        }
+        update_errors
+    } else {
+        let mut managers = Managers::default();
+        for_all! {
+            managers.__manager = !packages.__manager.is_empty();
+        }
+        execute!([], update, [], managers)
+    };
+    report_errors(&update_errors);
+    // Every manager is done by now, its error telling whether its updates went through
+    let mut changes = Vec::with_capacity(packages.total());
+    for_all! {
+        let error = update_errors
+            .iter()
+            .find(|e| e.manager() == "__manager")
+            .map(Error::message);
+        changes.extend(packages.__manager.iter().map(|package| {
+            let old_version = installed
+                .get(&("__manager", package.name().to_string()))
+                .cloned();
+            Change::new("__manager", PlanItem::upgrade(package, old_version))
+                .with_error(error.clone())
+        }));
     }
+    record_history("update", changes);
+    errors.extend(update_errors);
+    errors
+}
 
-    let mut managers = Managers::default();
+pub async fn export(path: &Path, pin: bool) -> Vec<Error> {
+    let (packages, mut errors) = get_results!([], list_explicit, Packages, []);
+    report_errors(&errors);
+    let mut manifest = Manifest::default();
     for_all! {
-        managers.__manager = !packages.__manager.is_empty();
+        if ARGS.managers.__manager && !errors.iter().any(|e| e.manager() == "__manager") {
+            let manager = managers::__Manager::new();
+            let mut entries = packages
+                .__manager
+                .iter()
+                .map(|package| manager.entry(package))
+                .collect::<Vec<_>>();
+            if !pin {
+                entries.iter_mut().for_each(|entry| entry.version = None);
+            }
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+            let section = Section {
+                packages: entries,
+                ..Default::default()
+            };
+            manifest.managers.insert("__manager".into(), section);
+        }
+    }
+    #[cfg(feature = "flatpak")]
+    if let Some(section) = manifest.managers.get_mut("flatpak") {
+        match managers::Flatpak::new().remotes() {
+            Ok(remotes) => {
+                section.remotes = remotes
+                    .into_iter()
+                    .map(|remote| (remote.name, remote.url))
+                    .collect()
+            }
+            Err(err) => {
+                let err = Error::Flatpak(err);
+                eprintln!("{}", err);
+                errors.push(err);
+            }
+        }
     }
+    let result = if path == Path::new("-") {
+        manifest.to_toml().map(|toml| print!("{}", toml))
+    } else {
+        manifest.save(path)
+    };
+    if let Err(err) = result {
+        let err = Error::Manifest(err);
+        eprintln!("{}", err);
+        errors.push(err);
+    }
+    errors
+}
 
-    let errors = execute!([], update, [], managers);
-    for error in errors {
-        println!("{error}");
+fn entry_plan(action: Action, entry: &Entry, old_version: Option<String>) -> PlanItem {
+    PlanItem {
+        action,
+        name: entry.name.clone(),
+        old_version,
+        new_version: entry.version.clone(),
+        download_size: None,
+    }
+}
+
+fn sync_plan(diff: &Diff, prune: bool) -> Vec<PlanItem> {
+    let mut plan = diff
+        .missing
+        .iter()
+        .map(|entry| entry_plan(Action::Install, entry, None))
+        .collect::<Vec<_>>();
+    plan.extend(
+        diff.mismatched.iter().map(|(entry, installed)| {
+            entry_plan(Action::Upgrade, entry, installed.version.clone())
+        }),
+    );
+    if prune {
+        plan.extend(diff.extra.iter().map(|installed| PlanItem {
+            new_version: None,
+            ..entry_plan(Action::Remove, installed, installed.version.clone())
+        }));
+    }
+    plan
+}
+
+pub async fn sync(path: &Path, prune: bool) -> Vec<Error> {
+    let manifest = match Manifest::load(path) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            let err = Error::Manifest(err);
+            eprintln!("{}", err);
+            return vec![err];
+        }
+    };
+    if !ARGS.dry_run {
+        elevate();
+    }
+    let (packages, mut errors) = get_results!([], list_explicit, Packages, []);
+    let mut plans = Plans::default();
+    for_all! {
+        let __manager_diff = manifest
+            .managers
+            .get("__manager")
+            .filter(|_| ARGS.managers.__manager)
+            .filter(|_| !errors.iter().any(|e| e.manager() == "__manager"))
+            .map(|section| {
+                let manager = managers::__Manager::new();
+                let installed = packages
+                    .__manager
+                    .iter()
+                    .map(|package| manager.entry(package))
+                    .collect::<Vec<_>>();
+                section.diff(&installed)
+            });
+        if let Some(diff) = &__manager_diff {
+            plans.__manager = sync_plan(diff, prune);
+        }
+    }
+    #[cfg(feature = "flatpak")]
+    let remotes = match manifest.managers.get("flatpak") {
+        Some(section) if ARGS.managers.flatpak && !section.remotes.is_empty() => {
+            match managers::Flatpak::new().remotes() {
+                Ok(existing) => section
+                    .remotes
+                    .iter()
+                    .filter(|(name, _)| !existing.iter().any(|remote| remote.name == **name))
+                    .collect::<Vec<_>>(),
+                Err(err) => {
+                    errors.push(Error::Flatpak(err));
+                    Vec::new()
+                }
+            }
+        }
+        _ => Vec::new(),
+    };
+
+    print_plans(&plans, &errors);
+    let mut total = 0;
+    for_all! {
+        total += plans.__manager.len();
+    }
+    #[cfg(feature = "flatpak")]
+    {
+        for (name, url) in &remotes {
+            println!(
+                "{} remote {} ({})",
+                FLATPAK_STYLE.apply_to("Flatpak:"),
+                name,
+                url
+            );
+        }
+        total += remotes.len();
+    }
+    if ARGS.dry_run || total == 0 {
+        return errors;
+    }
+    if !confirm("Do you want to apply these changes?") {
+        return errors;
+    }
+
+    let mut changes = Vec::new();
+    #[cfg(feature = "flatpak")]
+    for (name, url) in &remotes {
+        if let Err(err) = managers::Flatpak::new().add_remote(name, url, None) {
+            let err = Error::Flatpak(err);
+            eprintln!("{}", err);
+            errors.push(err);
+        }
+    }
+    for_all! {
+        if let Some(diff) = &__manager_diff {
+            let manager = managers::__Manager::new();
+            let wanted = diff.missing.iter().map(|entry| (entry, None)).chain(
+                diff.mismatched
+                    .iter()
+                    .map(|(entry, installed)| (entry, installed.version.clone())),
+            );
+            for (entry, old_version) in wanted {
+                let package = match manager.resolve(entry).await {
+                    Ok(Some(package)) => package,
+                    Ok(None) => {
+                        eprintln!("__Manager: {} not found, skipping", entry.name);
+                        continue;
+                    }
+                    Err(err) => {
+                        let err = Error::__Manager(err);
+                        eprintln!("{}", err);
+                        errors.push(err);
+                        continue;
+                    }
+                };
+                if let Some(version) = entry.version.as_deref() {
+                    if version != package.version() {
+                        eprintln!(
+                            "__Manager: {} {} is not available, skipping",
+                            entry.name, version
+                        );
+                        continue;
+                    }
+                }
+                __manager_pre_install(&package).await;
+                let result = manager.install(&package).await;
+                let item = match old_version {
+                    Some(_) => PlanItem::upgrade(&package, old_version),
+                    None => PlanItem::install(&package),
+                };
+                changes.push(
+                    Change::new("__manager", item)
+                        .with_error(result.as_ref().err().map(ToString::to_string)),
+                );
+                if let Err(err) = result {
+                    eprintln!("Failed to install {}: {}", entry.name, err);
+                    errors.push(Error::__Manager(err));
+                }
+            }
+            if prune {
+                let extra = packages.__manager.iter().filter(|package| {
+                    let name = manager.entry(package).name;
+                    diff.extra.iter().any(|entry| entry.name == name)
+                });
+                for package in extra {
+                    __manager_pre_uninstall(package).await;
+                    let result = manager.uninstall(package).await;
+                    changes.push(
+                        Change::new("__manager", PlanItem::remove(package))
+                            .with_error(result.as_ref().err().map(ToString::to_string)),
+                    );
+                    if let Err(err) = result {
+                        eprintln!("Failed to uninstall {}: {}", package.name(), err);
+                        errors.push(Error::__Manager(err));
+                    }
+                }
+            }
+        }
+    }
+    record_history("sync", changes);
+    errors
+}
+
+/// Keeps the changes matching the filters, dropping the transactions left empty.
+fn filter_history(
+    transactions: Vec<Transaction>,
+    package: Option<&str>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Vec<Transaction> {
+    transactions
+        .into_iter()
+        .filter(|transaction| {
+            let date = transaction.timestamp.date_naive();
+            since.is_none_or(|since| date >= since) && until.is_none_or(|until| date <= until)
+        })
+        .filter_map(|mut transaction| {
+            transaction.changes.retain(|change| {
+                ARGS.managers.contains(&change.manager)
+                    && package.is_none_or(|package| change.package.contains(package))
+            });
+            (!transaction.changes.is_empty()).then_some(transaction)
+        })
+        .collect()
+}
+
+fn print_transaction(transaction: &Transaction) {
+    println!("Transaction {}", transaction.id);
+    println!(
+        "Date:    {}",
+        transaction.timestamp.format("%Y-%m-%d %H:%M:%S %:z")
+    );
+    println!("User:    {}", transaction.user);
+    println!("Command: {}", transaction.command);
+    if let Some(id) = transaction.undoes {
+        println!("Undoes:  {}", id);
+    }
+    println!();
+    print_changes(&transaction.changes);
+}
+
+fn print_changes(changes: &[Change]) {
+    let mut tw = TabWriter::new(std::io::stdout());
+    let mut output = String::new();
+    for change in changes {
+        let versions = match (&change.old_version, &change.new_version) {
+            (Some(old), Some(new)) => format!("{} -> {}", old, new),
+            (Some(version), None) | (None, Some(version)) => version.clone(),
+            (None, None) => String::new(),
+        };
+        output.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            manager_label(&change.manager),
+            change.action,
+            change.package,
+            versions,
+            change.error.as_deref().unwrap_or("ok"),
+        ));
+    }
+    write!(&mut tw, "{}", output).expect("failed to write output");
+    tw.flush().expect("failed to flush output");
+}
+
+pub fn history(
+    id: Option<u64>,
+    package: Option<&str>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Vec<Error> {
+    let transactions = match history::load() {
+        Ok(transactions) => transactions,
+        Err(err) => {
+            let err = Error::History(err);
+            eprintln!("{}", err);
+            return vec![err];
+        }
+    };
+    let mut transactions = filter_history(transactions, package, since, until);
+    if let Some(id) = id {
+        transactions.retain(|transaction| transaction.id == id);
+    }
+    if let Some(format) = ARGS.output {
+        output::print_history(format, &transactions, &[]);
+        return vec![];
+    }
+    if let Some(id) = id {
+        match transactions.first() {
+            Some(transaction) => print_transaction(transaction),
+            None => println!("No transaction {}.", id),
+        }
+        return vec![];
+    }
+    if transactions.is_empty() {
+        println!("No history.");
+        return vec![];
+    }
+    let mut tw = TabWriter::new(std::io::stdout());
+    let mut output = String::new();
+    for transaction in &transactions {
+        let packages = transaction
+            .changes
+            .iter()
+            .map(|change| change.package.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        output.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\n",
+            transaction.id,
+            transaction.timestamp.format("%Y-%m-%d %H:%M"),
+            transaction.user,
+            transaction.command,
+            if transaction.succeeded() {
+                "ok"
+            } else {
+                "failed"
+            },
+            packages,
+        ));
+    }
+    write!(&mut tw, "{}", output).expect("failed to write output");
+    tw.flush().expect("failed to flush output");
+    vec![]
+}
+
+pub async fn undo(id: Option<u64>) -> Vec<Error> {
+    if !ARGS.dry_run {
+        elevate();
+    }
+    let transactions = match history::load() {
+        Ok(transactions) => transactions,
+        Err(err) => {
+            let err = Error::History(err);
+            eprintln!("{}", err);
+            return vec![err];
+        }
+    };
+    let transaction = match id {
+        Some(id) => transactions.into_iter().find(|t| t.id == id),
+        None => history::last_undoable(transactions),
+    };
+    let Some(transaction) = transaction else {
+        match id {
+            Some(id) => println!("No transaction {}.", id),
+            None => println!("Nothing to undo."),
+        }
+        return vec![];
+    };
+    // Undone in reverse order, failed changes having nothing to undo
+    let changes = transaction
+        .changes
+        .iter()
+        .rev()
+        .filter(|change| change.succeeded() && ARGS.managers.contains(&change.manager))
+        .collect::<Vec<_>>();
+    let mut plans = Plans::default();
+    for change in &changes {
+        for_all! {
+            if change.manager == "__manager" {
+                plans.__manager.push(change.undo());
+            }
+        }
+    }
+    print_plans(&plans, &[]);
+    if ARGS.dry_run || changes.is_empty() {
+        return vec![];
+    }
+    if !confirm(&format!(
+        "Do you want to undo transaction {}?",
+        transaction.id
+    )) {
+        return vec![];
+    }
+
+    let mut errors = Vec::new();
+    let mut reverted = Vec::with_capacity(changes.len());
+    for change in changes {
+        for_all! {
+            if change.manager == "__manager" {
+                let error = match managers::__Manager::new().revert(change).await {
+                    Ok(true) => None,
+                    Ok(false) => {
+                        let message = match &change.old_version {
+                            Some(version) => {
+                                format!("{} {} is not available", change.package, version)
+                            }
+                            None => {
+                                format!("Cannot undo the {} of {}", change.action, change.package)
+                            }
+                        };
+                        eprintln!("__Manager: {}", message);
+                        Some(message)
+                    }
+                    Err(err) => {
+                        let message = err.to_string();
+                        eprintln!(
+                            "Failed to undo the {} of {}: {}",
+                            change.action, change.package, err
+                        );
+                        errors.push(Error::__Manager(err));
+                        Some(message)
+                    }
+                };
+                reverted.push(Change::new("__manager", change.undo()).with_error(error));
+            }
+        }
+    }
+    if !reverted.is_empty() {
+        if let Err(err) = history::record_undo(transaction.id, reverted) {
+            eprintln!("Failed to record history: {}", err);
+        }
+    }
+    errors
+}
+
+fn load_pins() -> Result<Pins, Error> {
+    Pins::load().map_err(|err| {
+        let err = Error::Pins(err);
+        eprintln!("{}", err);
+        err
+    })
+}
+
+fn save_pins(pins: &Pins, errors: &mut Vec<Error>) {
+    if let Err(err) = pins.save() {
+        let err = Error::Pins(err);
+        eprintln!("{}", err);
+        errors.push(err);
+    }
+}
+
+pub fn list_pins() -> Vec<Error> {
+    let pins = match load_pins() {
+        Ok(pins) => pins,
+        Err(err) => return vec![err],
+    };
+    let pins = pins
+        .pins
+        .into_iter()
+        .filter(|pin| ARGS.managers.contains(&pin.manager))
+        .collect::<Vec<_>>();
+    if let Some(format) = ARGS.output {
+        output::print_pins(format, &pins, &[]);
+        return vec![];
+    }
+    if pins.is_empty() {
+        println!("No pinned packages.");
+        return vec![];
+    }
+    let mut tw = TabWriter::new(std::io::stdout());
+    let mut output = String::new();
+    for pin in &pins {
+        output.push_str(&format!(
+            "{}\t{}\t{}\n",
+            manager_label(&pin.manager),
+            pin.package,
+            pin.version.as_deref().unwrap_or("*"),
+        ));
+    }
+    write!(&mut tw, "{}", output).expect("failed to write output");
+    tw.flush().expect("failed to flush output");
+    vec![]
+}
+
+pub async fn pin(target: &str, version: Option<&str>) -> Vec<Error> {
+    let (managers, query) = ARGS.managers.target(target);
+    let query: Arc<str> = Arc::from(query);
+    let (packages, mut errors) = get_results!([query,], find, Package, [&query,], managers);
+    report_errors(&errors);
+    let mut owners = Vec::new();
+    for_all! {
+        if packages.__manager.is_some() {
+            owners.push("__manager");
+        }
+    }
+    owners.sort_by_key(|manager| CONFIG.priority(manager));
+    let Some(&selected_manager) = owners.first() else {
+        println!("{} is not installed.", query);
+        return errors;
+    };
+    let mut pins = match load_pins() {
+        Ok(pins) => pins,
+        Err(err) => {
+            errors.push(err);
+            return errors;
+        }
+    };
+    for_all! {
+        let package = packages
+            .__manager
+            .as_ref()
+            .filter(|_| selected_manager == "__manager");
+        if let Some(package) = package {
+            // The manager's own hold would also block updates to a pinned version
+            let held = version.is_none();
+            let was_held = pins
+                .get("__manager", package.name())
+                .is_some_and(|pin| pin.version.is_none());
+            if held || was_held {
+                // Written before elevating, for the file to belong to the user
+                save_pins(&pins, &mut errors);
+                elevate();
+                if let Err(err) = managers::__Manager::new().set_held(package, held).await {
+                    let err = Error::__Manager(err);
+                    eprintln!("{}", err);
+                    errors.push(err);
+                }
+            }
+            pins.insert(Pin {
+                manager: "__manager".into(),
+                package: package.name().into(),
+                version: version.map(String::from),
+            });
+            save_pins(&pins, &mut errors);
+            println!(
+                "{} {} pinned at {}",
+                manager_label("__manager"),
+                package.name(),
+                version.unwrap_or(package.version()),
+            );
+        }
+    }
+    errors
+}
+
+pub async fn unpin(target: &str) -> Vec<Error> {
+    let (managers, name) = ARGS.managers.target(target);
+    let mut pins = match load_pins() {
+        Ok(pins) => pins,
+        Err(err) => return vec![err],
+    };
+    let unpinned = pins
+        .pins
+        .iter()
+        .filter(|pin| managers.contains(&pin.manager) && pin.package == name)
+        .cloned()
+        .collect::<Vec<_>>();
+    if unpinned.is_empty() {
+        println!("{} is not pinned.", name);
+        return vec![];
+    }
+    // Only pins without a version are held by the managers themselves
+    if unpinned.iter().any(|pin| pin.version.is_none()) {
+        elevate();
+    }
+    let mut errors = Vec::new();
+    for pin in &unpinned {
+        pins.remove(&pin.manager, &pin.package);
+        for_all! {
+            if pin.manager == "__manager" && pin.version.is_none() {
+                let manager = managers::__Manager::new();
+                let result = match manager.find(&pin.package).await {
+                    Ok(Some(package)) => manager.set_held(&package, false).await,
+                    Ok(None) => Ok(()),
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = result {
+                    let err = Error::__Manager(err);
+                    eprintln!("{}", err);
+                    errors.push(err);
+                }
+            }
+        }
+        println!("{} {} unpinned", manager_label(&pin.manager), pin.package);
+    }
+    save_pins(&pins, &mut errors);
+    errors
+}
+
+#[cfg(feature = "flatpak")]
+pub fn remotes(installation: Option<&str>) -> Vec<Error> {
+    let remotes = match managers::Flatpak::new().remotes() {
+        Ok(remotes) => remotes,
+        Err(err) => {
+            let err = Error::Flatpak(err);
+            eprintln!("{}", err);
+            return vec![err];
+        }
+    };
+    let remotes = remotes
+        .into_iter()
+        .filter(|remote| installation.is_none_or(|i| remote.installation.to_string() == i))
+        .collect::<Vec<_>>();
+    if let Some(format) = ARGS.output {
+        output::print_remotes(format, &remotes, &[]);
+        return vec![];
+    }
+    if remotes.is_empty() {
+        println!("No remotes.");
+        return vec![];
+    }
+    let mut tw = TabWriter::new(std::io::stdout());
+    let mut output = String::new();
+    for remote in &remotes {
+        output.push_str(&format!(
+            "{}\t{}\t{}{}\n",
+            FLATPAK_STYLE.apply_to(&remote.name),
+            remote.url,
+            remote.installation,
+            if remote.disabled { " (disabled)" } else { "" },
+        ));
+    }
+    write!(&mut tw, "{}", output).expect("failed to write output");
+    tw.flush().expect("failed to flush output");
+    vec![]
+}
+
+#[cfg(feature = "flatpak")]
+pub fn add_remote(name: &str, url: &str, installation: Option<&str>) -> Vec<Error> {
+    println!(
+        "{} remote {} ({})",
+        FLATPAK_STYLE.apply_to("Flatpak:"),
+        name,
+        url
+    );
+    if ARGS.dry_run {
+        return vec![];
+    }
+    let installation: Option<managers::flatpak::Installation> =
+        installation.map(|installation| installation.to_string().into());
+    match managers::Flatpak::new().add_remote(name, url, installation.as_ref()) {
+        Ok(()) => vec![],
+        Err(err) => {
+            let err = Error::Flatpak(err);
+            eprintln!("{}", err);
+            vec![err]
+        }
+    }
+}
+
+#[cfg(feature = "flatpak")]
+pub fn remove_remote(name: &str, installation: Option<&str>) -> Vec<Error> {
+    if ARGS.dry_run {
+        println!(
+            "{} remove remote {}",
+            FLATPAK_STYLE.apply_to("Flatpak:"),
+            name
+        );
+        return vec![];
+    }
+    if !confirm(&format!("Do you want to remove the remote {}?", name)) {
+        return vec![];
+    }
+    let installation: Option<managers::flatpak::Installation> =
+        installation.map(|installation| installation.to_string().into());
+    match managers::Flatpak::new().remove_remote(name, installation.as_ref()) {
+        Ok(()) => vec![],
+        Err(err) => {
+            let err = Error::Flatpak(err);
+            eprintln!("{}", err);
+            vec![err]
+        }
     }
 }
@@ -0,0 +1,70 @@
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use unipac_managers::utils::config;
+
+/// Top-level keys of `$XDG_CONFIG_HOME/unipac/config.toml`.
+///
+/// Manager specific options live in their own tables (`[flatpak]`, `[cargo]`,
+/// `[aur]`) and are read by the managers themselves.
+pub static CONFIG: Lazy<Config> = Lazy::new(config::get);
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Managers enabled when none is given on the command line.
+    pub managers: Option<Vec<String>>,
+    /// Managers whose packages are listed first when installing.
+    pub priority: Vec<String>,
+    /// Program used to review PKGBUILDs, before `$EDITOR`.
+    pub editor: Option<String>,
+    /// Style of each manager, like `"blue"` or `"red.bold"`.
+    pub colors: HashMap<String, String>,
+    pub prompts: Prompts,
+}
+impl Config {
+    pub fn is_default_manager(&self, manager: &str) -> bool {
+        self.managers
+            .as_ref()
+            .is_none_or(|managers| managers.iter().any(|m| m == manager))
+    }
+
+    /// Rank of `manager` in the install selection, lower coming first.
+    pub fn priority(&self, manager: &str) -> usize {
+        self.priority
+            .iter()
+            .position(|m| m == manager)
+            .unwrap_or(self.priority.len())
+    }
+
+    #[cfg(feature = "aur")]
+    pub fn editor(&self) -> String {
+        self.editor
+            .clone()
+            .or_else(|| std::env::var("EDITOR").ok())
+            .unwrap_or_else(|| "less".into())
+    }
+
+    pub fn style(&self, manager: &str, default: console::Style) -> console::Style {
+        self.colors
+            .get(manager)
+            .map_or(default, |style| console::Style::from_dotted_str(style))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Prompts {
+    /// Ask before uninstalling or updating packages.
+    pub confirm: bool,
+    /// Offer to review the PKGBUILD of AUR packages before building them.
+    pub review_pkgbuild: bool,
+}
+impl Default for Prompts {
+    fn default() -> Self {
+        Self {
+            confirm: true,
+            review_pkgbuild: true,
+        }
+    }
+}
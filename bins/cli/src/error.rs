@@ -0,0 +1,146 @@
+use crate::output::ErrorRecord;
+use std::fmt::Display;
+use unipac_macros::for_all_attrs;
+use unipac_managers::managers::{self, ErrorKind, ManagerError};
+use unipac_managers::{history, manifest, pins};
+
+// Exit codes, following sysexits.h
+const EXIT_FAILURE: i32 = 1;
+const EXIT_DATAERR: i32 = 65;
+const EXIT_NOINPUT: i32 = 66;
+const EXIT_UNAVAILABLE: i32 = 69;
+const EXIT_SOFTWARE: i32 = 70;
+const EXIT_TEMPFAIL: i32 = 75;
+const EXIT_NOPERM: i32 = 77;
+
+#[for_all_attrs]
+#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Error {
+    __Manager(managers::__manager::Error),
+    Manifest(manifest::Error),
+    History(history::Error),
+    Pins(pins::Error),
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.display_name(), self.inner())
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.inner())
+    }
+}
+
+impl Error {
+    fn inner(&self) -> &(dyn std::error::Error + 'static) {
+        match self {
+            #[cfg(feature = "pacman")]
+            Self::Pacman(err) => err,
+            #[cfg(feature = "aur")]
+            Self::AUR(err) => err,
+            #[cfg(feature = "flatpak")]
+            Self::Flatpak(err) => err,
+            #[cfg(feature = "snap")]
+            Self::Snap(err) => err,
+            #[cfg(feature = "git")]
+            Self::Git(err) => err,
+            #[cfg(feature = "cargo")]
+            Self::Cargo(err) => err,
+            Self::Manifest(err) => err,
+            Self::History(err) => err,
+            Self::Pins(err) => err,
+        }
+    }
+
+    fn display_name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "pacman")]
+            Self::Pacman(_) => "Pacman",
+            #[cfg(feature = "aur")]
+            Self::AUR(_) => "AUR",
+            #[cfg(feature = "flatpak")]
+            Self::Flatpak(_) => "Flatpak",
+            #[cfg(feature = "snap")]
+            Self::Snap(_) => "Snap",
+            #[cfg(feature = "git")]
+            Self::Git(_) => "Git",
+            #[cfg(feature = "cargo")]
+            Self::Cargo(_) => "Cargo",
+            Self::Manifest(_) => "Manifest",
+            Self::History(_) => "History",
+            Self::Pins(_) => "Pins",
+        }
+    }
+
+    pub fn manager(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "pacman")]
+            Self::Pacman(_) => "pacman",
+            #[cfg(feature = "aur")]
+            Self::AUR(_) => "aur",
+            #[cfg(feature = "flatpak")]
+            Self::Flatpak(_) => "flatpak",
+            #[cfg(feature = "snap")]
+            Self::Snap(_) => "snap",
+            #[cfg(feature = "git")]
+            Self::Git(_) => "git",
+            #[cfg(feature = "cargo")]
+            Self::Cargo(_) => "cargo",
+            Self::Manifest(_) => "manifest",
+            Self::History(_) => "history",
+            Self::Pins(_) => "pins",
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            #[cfg(feature = "pacman")]
+            Self::Pacman(err) => err.kind(),
+            #[cfg(feature = "aur")]
+            Self::AUR(err) => err.kind(),
+            #[cfg(feature = "flatpak")]
+            Self::Flatpak(err) => err.kind(),
+            #[cfg(feature = "snap")]
+            Self::Snap(err) => err.kind(),
+            #[cfg(feature = "git")]
+            Self::Git(err) => err.kind(),
+            #[cfg(feature = "cargo")]
+            Self::Cargo(err) => err.kind(),
+            Self::Manifest(err) => err.kind(),
+            Self::History(err) => err.kind(),
+            Self::Pins(err) => err.kind(),
+        }
+    }
+
+    /// The error without the manager name.
+    pub fn message(&self) -> String {
+        self.inner().to_string()
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self.kind() {
+            ErrorKind::Network => EXIT_UNAVAILABLE,
+            ErrorKind::Parse => EXIT_DATAERR,
+            ErrorKind::Command => EXIT_SOFTWARE,
+            ErrorKind::PermissionDenied => EXIT_NOPERM,
+            ErrorKind::NotFound => EXIT_NOINPUT,
+            ErrorKind::Locked => EXIT_TEMPFAIL,
+            ErrorKind::Conflict | ErrorKind::Other => EXIT_FAILURE,
+        }
+    }
+
+    pub fn to_record(&self) -> ErrorRecord {
+        ErrorRecord {
+            manager: self.manager(),
+            kind: self.kind().to_string(),
+            message: self.message(),
+        }
+    }
+}
+
+/// Exit code of a command that ended with `errors`, the first one deciding.
+pub fn exit_code(errors: &[Error]) -> i32 {
+    errors.first().map_or(0, Error::exit_code)
+}
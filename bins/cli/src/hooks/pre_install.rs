@@ -5,6 +5,8 @@ pub async fn pacman_pre_install(_package: &pacman::Package) {}
 
 #[cfg(feature = "aur")]
 pub async fn aur_pre_install(package: &aur::Package) {
+    use crate::args::ARGS;
+    use crate::config::CONFIG;
     use dialoguer::Confirm;
     use unipac_managers::utils::dirs::{download_and_extract_aur_archive, get_pkgbuild_path};
 
@@ -16,6 +18,9 @@ pub async fn aur_pre_install(package: &aur::Package) {
         );
         std::process::exit(1);
     }
+    if !CONFIG.prompts.review_pkgbuild || !ARGS.interactive() {
+        return;
+    }
     let might_show_pkgbuild = Confirm::new()
         .with_prompt(format!(
             "Do you want to show/edit the PKGBUILD for {}?",
@@ -34,8 +39,7 @@ pub async fn aur_pre_install(package: &aur::Package) {
         return;
     }
     let path = get_pkgbuild_path(&package.name);
-    let editor = std::env::var("EDITOR").unwrap_or("less".into());
-    let result = std::process::Command::new(editor)
+    let result = std::process::Command::new(CONFIG.editor())
         .arg(path)
         .spawn()
         .and_then(|mut p| p.wait());
@@ -64,5 +68,8 @@ pub async fn flatpak_pre_install(_package: &flatpak::Package) {}
 #[cfg(feature = "snap")]
 pub async fn snap_pre_install(_package: &snap::Package) {}
 
+#[cfg(feature = "git")]
+pub async fn git_pre_install(_package: &git::Package) {}
+
 #[cfg(feature = "cargo")]
 pub async fn cargo_pre_install(_package: &cargo::Package) {}
@@ -12,5 +12,8 @@ pub async fn flatpak_pre_uninstall(_package: &flatpak::Package) {}
 #[cfg(feature = "snap")]
 pub async fn snap_pre_uninstall(_package: &snap::Package) {}
 
+#[cfg(feature = "git")]
+pub async fn git_pre_uninstall(_package: &git::Package) {}
+
 #[cfg(feature = "cargo")]
 pub async fn cargo_pre_uninstall(_package: &cargo::Package) {}
@@ -5,6 +5,8 @@ pub async fn pacman_pre_update(_packages: &Vec<pacman::Package>) {}
 
 #[cfg(feature = "aur")]
 pub async fn aur_pre_update(packages: &Vec<aur::Package>) {
+    use crate::args::ARGS;
+    use crate::config::CONFIG;
     use dialoguer::Confirm;
     use unipac_managers::utils::dirs::{download_and_extract_aur_archive, get_pkgbuild_path};
 
@@ -20,6 +22,9 @@ pub async fn aur_pre_update(packages: &Vec<aur::Package>) {
                 std::process::exit(1);
             }
         }
+        if !CONFIG.prompts.review_pkgbuild || !ARGS.interactive() {
+            return;
+        }
         for package in packages {
             let Ok(might_show_pkgbuild) = Confirm::new()
                 .with_prompt(format!(
@@ -34,7 +39,7 @@ pub async fn aur_pre_update(packages: &Vec<aur::Package>) {
             };
             if might_show_pkgbuild {
                 let path = get_pkgbuild_path(&package.name);
-                let result = std::process::Command::new("less")
+                let result = std::process::Command::new(CONFIG.editor())
                     .arg(path)
                     .spawn()
                     .and_then(|mut p| p.wait());
@@ -52,5 +57,8 @@ pub async fn flatpak_pre_update(_packages: &Vec<flatpak::Package>) {}
 #[cfg(feature = "snap")]
 pub async fn snap_pre_update(_packages: &Vec<snap::Package>) {}
 
+#[cfg(feature = "git")]
+pub async fn git_pre_update(_packages: &Vec<git::Package>) {}
+
 #[cfg(feature = "cargo")]
 pub async fn cargo_pre_update(_packages: &Vec<cargo::Package>) {}
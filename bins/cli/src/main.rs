@@ -1,6 +1,9 @@
 mod args;
 mod commands;
+mod config;
+mod error;
 mod hooks;
+mod output;
 mod style;
 mod utils;
 
@@ -12,31 +15,69 @@ async fn main() {
         println!("No command specified");
         return;
     };
-    match command {
+    let errors = match command {
         args::Command::List { updates } => {
             if *updates {
-                commands::list_updates().await;
+                commands::list_updates().await
             } else {
-                commands::list().await;
+                commands::list().await
             }
         }
-        args::Command::Search { query } => {
-            commands::search(query).await;
-        }
-        args::Command::Install { query } => {
-            commands::install(query).await;
-        }
-        args::Command::Uninstall { query } => {
-            commands::uninstall(query).await;
-        }
-        args::Command::Update { query, list, count } => {
+        args::Command::Search { query } => commands::search(query).await,
+        args::Command::Info { query } => commands::info(query).await,
+        args::Command::Owns { path } => commands::owns(path).await,
+        args::Command::Install { queries, flatpak } => commands::install(queries, flatpak).await,
+        args::Command::Uninstall { queries } => commands::uninstall(queries).await,
+        args::Command::Autoremove => commands::autoremove().await,
+        args::Command::Update {
+            queries,
+            list,
+            count,
+            select,
+        } => {
             if *list {
-                commands::list_updates().await;
+                commands::list_updates().await
             } else if *count {
-                commands::count_updates().await;
+                commands::count_updates().await
             } else {
-                commands::update(query.as_deref()).await;
+                commands::update(queries, *select).await
             }
         }
-    }
+        args::Command::Export { file, pin } => commands::export(file, *pin).await,
+        args::Command::Sync { file, prune } => commands::sync(file, *prune).await,
+        args::Command::History {
+            id,
+            package,
+            since,
+            until,
+        } => commands::history(*id, package.as_deref(), *since, *until),
+        args::Command::Pin { query, version } => match query {
+            Some(query) => commands::pin(query, version.as_deref()).await,
+            None => commands::list_pins(),
+        },
+        args::Command::Unpin { query } => commands::unpin(query).await,
+        args::Command::Undo { id } => commands::undo(*id).await,
+        args::Command::Cache { action, keep } => match action {
+            args::CacheAction::Stats => commands::cache_stats(*keep).await,
+            args::CacheAction::Clean => commands::clean_cache(*keep).await,
+        },
+        args::Command::Doctor { check } => match check {
+            args::DoctorCheck::Duplicates => commands::duplicates().await,
+        },
+        #[cfg(feature = "flatpak")]
+        args::Command::Remote {
+            action,
+            installation,
+        } => {
+            let installation = installation.as_deref();
+            match action {
+                args::RemoteAction::List => commands::remotes(installation),
+                args::RemoteAction::Add { name, url } => {
+                    commands::add_remote(name, url, installation)
+                }
+                args::RemoteAction::Remove { name } => commands::remove_remote(name, installation),
+            }
+        }
+    };
+    std::process::exit(error::exit_code(&errors));
 }
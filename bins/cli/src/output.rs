@@ -0,0 +1,429 @@
+use crate::args::OutputFormat;
+use serde::Serialize;
+use std::collections::HashMap;
+use unipac_managers::history::Transaction;
+#[cfg(feature = "flatpak")]
+use unipac_managers::managers::flatpak::Remote;
+use unipac_managers::managers::{CacheUsage, PackageDetails, PackageInfo, PlanItem};
+use unipac_managers::pins::Pin;
+
+#[derive(Serialize)]
+pub struct PackageRecord {
+    pub manager: &'static str,
+    pub name: String,
+    /// What the manager knows the package by besides its name.
+    pub id: Option<String>,
+    pub version: Option<String>,
+    pub new_version: Option<String>,
+    pub description: Option<String>,
+    pub source: Option<String>,
+    /// Installed size in bytes, when the manager knows it.
+    pub size: Option<u64>,
+}
+impl PackageRecord {
+    pub fn new<P>(manager: &'static str, package: &P) -> Self
+    where
+        P: PackageInfo,
+    {
+        Self {
+            manager,
+            name: package.name().into(),
+            id: package.identifier().map(String::from),
+            version: Some(package.version().into()),
+            new_version: None,
+            description: package.description().map(String::from),
+            source: package.origin().map(String::from),
+            size: package.installed_size(),
+        }
+    }
+
+    /// Record for an available update, `package` being the new version.
+    pub fn update<P>(manager: &'static str, package: &P, installed: Option<&String>) -> Self
+    where
+        P: PackageInfo,
+    {
+        Self {
+            version: installed.cloned(),
+            new_version: Some(package.version().into()),
+            ..Self::new(manager, package)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DetailsRecord<'a> {
+    pub manager: &'static str,
+    #[serde(flatten)]
+    pub details: &'a PackageDetails,
+}
+
+#[derive(Serialize)]
+pub struct PlanRecord {
+    pub manager: &'static str,
+    pub action: String,
+    pub name: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub download_size: Option<u64>,
+}
+impl PlanRecord {
+    pub fn new(manager: &'static str, item: &PlanItem) -> Self {
+        Self {
+            manager,
+            action: item.action.to_string(),
+            name: item.name.clone(),
+            old_version: item.old_version.clone(),
+            new_version: item.new_version.clone(),
+            download_size: item.download_size,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CountRecord {
+    pub manager: &'static str,
+    pub count: usize,
+}
+
+#[derive(Serialize)]
+pub struct CacheRecord {
+    pub manager: &'static str,
+    #[serde(flatten)]
+    pub usage: CacheUsage,
+    /// Bytes `cache clean` freed, absent when nothing was cleaned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freed: Option<u64>,
+}
+
+/// One of the installed copies of a software.
+#[derive(Serialize)]
+pub struct CopyRecord {
+    pub manager: &'static str,
+    pub name: String,
+    pub version: String,
+    /// Commands for which this copy is the one found in `$PATH`.
+    pub wins: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DuplicateRecord {
+    /// Names the copies share.
+    pub names: Vec<String>,
+    pub copies: Vec<CopyRecord>,
+}
+
+#[derive(Serialize)]
+pub struct ErrorRecord {
+    pub manager: &'static str,
+    pub kind: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct PackagesDocument<'a> {
+    packages: &'a [PackageRecord],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct DetailsDocument<'a> {
+    packages: &'a [DetailsRecord<'a>],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct PlanDocument<'a> {
+    plan: &'a [PlanRecord],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct CountsDocument<'a> {
+    total: usize,
+    counts: &'a [CountRecord],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct CachesDocument<'a> {
+    size: u64,
+    reclaimable: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    freed: Option<u64>,
+    caches: &'a [CacheRecord],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct HistoryDocument<'a> {
+    transactions: &'a [Transaction],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct PinsDocument<'a> {
+    pins: &'a [Pin],
+    errors: &'a [ErrorRecord],
+}
+
+#[cfg(feature = "flatpak")]
+#[derive(Serialize)]
+struct RemotesDocument<'a> {
+    remotes: &'a [Remote],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct DuplicatesDocument<'a> {
+    duplicates: &'a [DuplicateRecord],
+    errors: &'a [ErrorRecord],
+}
+
+#[derive(Serialize)]
+struct ErrorLine<'a> {
+    error: &'a ErrorRecord,
+}
+
+/// Installed versions, indexed by manager and package name.
+pub type InstalledVersions = HashMap<(&'static str, String), String>;
+
+fn tsv_field(field: Option<&str>) -> String {
+    field.unwrap_or_default().replace(['\t', '\n'], " ")
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    println!(
+        "{}",
+        serde_json::to_string(value).expect("Failed to serialize output")
+    );
+}
+
+fn print_errors(format: OutputFormat, errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => {}
+        OutputFormat::Ndjson => {
+            for error in errors {
+                print_json(&ErrorLine { error });
+            }
+        }
+        OutputFormat::Tsv => {
+            for error in errors {
+                eprintln!("{}\t{}", error.manager, tsv_field(Some(&error.message)));
+            }
+        }
+    }
+}
+
+pub fn print_packages(format: OutputFormat, packages: &[PackageRecord], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&PackagesDocument { packages, errors }),
+        OutputFormat::Ndjson => packages.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for package in packages {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    package.manager,
+                    tsv_field(Some(&package.name)),
+                    tsv_field(package.version.as_deref()),
+                    tsv_field(package.new_version.as_deref()),
+                    tsv_field(package.description.as_deref()),
+                    tsv_field(package.source.as_deref()),
+                    tsv_field(package.id.as_deref()),
+                );
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+pub fn print_details(format: OutputFormat, packages: &[DetailsRecord], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&DetailsDocument { packages, errors }),
+        OutputFormat::Ndjson => packages.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for package in packages {
+                let details = package.details;
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    package.manager,
+                    tsv_field(Some(&details.name)),
+                    tsv_field(Some(&details.version)),
+                    details.installed,
+                    tsv_field(details.description.as_deref()),
+                    tsv_field(details.homepage.as_deref()),
+                    tsv_field(details.origin.as_deref()),
+                    tsv_field(details.maintainer.as_deref()),
+                    tsv_field(Some(&details.licenses.join(","))),
+                    details
+                        .installed_size
+                        .map(|size| size.to_string())
+                        .unwrap_or_default(),
+                    details
+                        .install_date
+                        .map(|date| date.to_rfc3339())
+                        .unwrap_or_default(),
+                );
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+pub fn print_counts(format: OutputFormat, counts: &[CountRecord], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&CountsDocument {
+            total: counts.iter().map(|c| c.count).sum(),
+            counts,
+            errors,
+        }),
+        OutputFormat::Ndjson => counts.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for count in counts {
+                println!("{}\t{}", count.manager, count.count);
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+pub fn print_caches(format: OutputFormat, caches: &[CacheRecord], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&CachesDocument {
+            size: caches.iter().map(|c| c.usage.size).sum(),
+            reclaimable: caches.iter().map(|c| c.usage.reclaimable).sum(),
+            freed: caches.iter().map(|c| c.freed).sum(),
+            caches,
+            errors,
+        }),
+        OutputFormat::Ndjson => caches.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for cache in caches {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    cache.manager,
+                    cache.usage.size,
+                    cache.usage.reclaimable,
+                    tsv_field(cache.freed.map(|freed| freed.to_string()).as_deref()),
+                );
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+pub fn print_plan(format: OutputFormat, plan: &[PlanRecord], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&PlanDocument { plan, errors }),
+        OutputFormat::Ndjson => plan.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for item in plan {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    item.manager,
+                    item.action,
+                    tsv_field(Some(&item.name)),
+                    tsv_field(item.old_version.as_deref()),
+                    tsv_field(item.new_version.as_deref()),
+                    item.download_size
+                        .map(|size| size.to_string())
+                        .unwrap_or_default(),
+                );
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+pub fn print_history(format: OutputFormat, transactions: &[Transaction], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&HistoryDocument {
+            transactions,
+            errors,
+        }),
+        OutputFormat::Ndjson => transactions.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for transaction in transactions {
+                for change in &transaction.changes {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                        transaction.id,
+                        transaction.timestamp.to_rfc3339(),
+                        tsv_field(Some(&transaction.user)),
+                        transaction.command,
+                        tsv_field(Some(&change.manager)),
+                        change.action,
+                        tsv_field(Some(&change.package)),
+                        tsv_field(change.old_version.as_deref()),
+                        tsv_field(change.new_version.as_deref()),
+                        tsv_field(change.error.as_deref()),
+                    );
+                }
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+pub fn print_pins(format: OutputFormat, pins: &[Pin], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&PinsDocument { pins, errors }),
+        OutputFormat::Ndjson => pins.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for pin in pins {
+                println!(
+                    "{}\t{}\t{}",
+                    tsv_field(Some(&pin.manager)),
+                    tsv_field(Some(&pin.package)),
+                    tsv_field(pin.version.as_deref()),
+                );
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+#[cfg(feature = "flatpak")]
+pub fn print_remotes(format: OutputFormat, remotes: &[Remote], errors: &[ErrorRecord]) {
+    match format {
+        OutputFormat::Json => print_json(&RemotesDocument { remotes, errors }),
+        OutputFormat::Ndjson => remotes.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for remote in remotes {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    tsv_field(Some(&remote.name)),
+                    tsv_field(Some(&remote.url)),
+                    remote.installation,
+                    remote.disabled,
+                );
+            }
+        }
+    }
+    print_errors(format, errors);
+}
+
+pub fn print_duplicates(
+    format: OutputFormat,
+    duplicates: &[DuplicateRecord],
+    errors: &[ErrorRecord],
+) {
+    match format {
+        OutputFormat::Json => print_json(&DuplicatesDocument { duplicates, errors }),
+        OutputFormat::Ndjson => duplicates.iter().for_each(print_json),
+        OutputFormat::Tsv => {
+            for duplicate in duplicates {
+                for copy in &duplicate.copies {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        tsv_field(Some(&duplicate.names.join(","))),
+                        copy.manager,
+                        tsv_field(Some(&copy.name)),
+                        tsv_field(Some(&copy.version)),
+                        tsv_field(Some(&copy.wins.join(","))),
+                    );
+                }
+            }
+        }
+    }
+    print_errors(format, errors);
+}
@@ -1,12 +1,24 @@
+use crate::config::CONFIG;
 use once_cell::sync::Lazy;
 
 #[cfg(feature = "pacman")]
-pub static PACMAN_STYLE: Lazy<console::Style> = Lazy::new(|| console::Style::new().blue());
+pub static PACMAN_STYLE: Lazy<console::Style> =
+    Lazy::new(|| CONFIG.style("pacman", console::Style::new().blue()));
 #[cfg(feature = "aur")]
-pub static AUR_STYLE: Lazy<console::Style> = Lazy::new(|| console::Style::new().red());
+pub static AUR_STYLE: Lazy<console::Style> =
+    Lazy::new(|| CONFIG.style("aur", console::Style::new().red()));
 #[cfg(feature = "flatpak")]
-pub static FLATPAK_STYLE: Lazy<console::Style> = Lazy::new(|| console::Style::new().green());
+pub static FLATPAK_STYLE: Lazy<console::Style> =
+    Lazy::new(|| CONFIG.style("flatpak", console::Style::new().green()));
 #[cfg(feature = "snap")]
-pub static SNAP_STYLE: Lazy<console::Style> = Lazy::new(|| console::Style::new().yellow());
+pub static SNAP_STYLE: Lazy<console::Style> =
+    Lazy::new(|| CONFIG.style("snap", console::Style::new().yellow()));
+#[cfg(feature = "git")]
+pub static GIT_STYLE: Lazy<console::Style> =
+    Lazy::new(|| CONFIG.style("git", console::Style::new().magenta()));
 #[cfg(feature = "cargo")]
-pub static CARGO_STYLE: Lazy<console::Style> = Lazy::new(|| console::Style::new().red());
+pub static CARGO_STYLE: Lazy<console::Style> =
+    Lazy::new(|| CONFIG.style("cargo", console::Style::new().red()));
+
+pub static WARNING_STYLE: Lazy<console::Style> =
+    Lazy::new(|| CONFIG.style("warning", console::Style::new().yellow()));
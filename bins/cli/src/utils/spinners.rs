@@ -1,8 +1,21 @@
 use crate::args::Managers;
 use crate::style::*;
 // use crate::ARGS;
-use indicatif::{MultiProgress, ProgressBar};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use once_cell::sync::Lazy;
 use unipac_macros::{for_all, for_all_attrs};
+use unipac_managers::managers::ProgressEvent;
+
+static STEPS_STYLE: Lazy<ProgressStyle> = Lazy::new(|| {
+    ProgressStyle::with_template("{spinner} {msg} [{bar:30}] {pos}/{len} ({eta})")
+        .expect("Invalid progress template")
+        .progress_chars("=> ")
+});
+static BYTES_STYLE: Lazy<ProgressStyle> = Lazy::new(|| {
+    ProgressStyle::with_template("{spinner} {msg} [{bar:30}] {bytes}/{total_bytes} ({eta})")
+        .expect("Invalid progress template")
+        .progress_chars("=> ")
+});
 
 #[for_all_attrs]
 #[derive(Clone)]
@@ -39,6 +52,8 @@ impl Spinners {
             flatpak: flatpak_spinner,
             #[cfg(feature = "snap")]
             snap: snap_spinner,
+            #[cfg(feature = "git")]
+            git: git_spinner,
             #[cfg(feature = "cargo")]
             cargo: cargo_spinner,
         }
@@ -58,6 +73,68 @@ impl Spinners {
         }
     }
 
+    /// Shows `event`, turning the spinner into a bar while steps or
+    /// downloads are counted and back into a spinner on other events.
+    #[for_all_attrs]
+    pub fn __manager_set_progress(&self, event: ProgressEvent) {
+        let Some(spinner) = &self.__manager else {
+            return;
+        };
+        let message = match event {
+            ProgressEvent::Started { .. } => {
+                spinner.set_style(ProgressStyle::default_spinner());
+                spinner.set_message(__MANAGER_STYLE.apply_to("__Manager...").to_string());
+                return;
+            }
+            ProgressEvent::Step {
+                step,
+                total,
+                package,
+            } => {
+                spinner.set_style(STEPS_STYLE.clone());
+                spinner.set_length(total as u64);
+                spinner.set_position(step as u64);
+                if !package.is_empty() {
+                    spinner.println(format!(
+                        "{} {}",
+                        __MANAGER_STYLE.apply_to("__Manager:"),
+                        package
+                    ));
+                }
+                package
+            }
+            ProgressEvent::Bytes {
+                file,
+                done,
+                total: Some(total),
+            } => {
+                spinner.set_style(BYTES_STYLE.clone());
+                spinner.set_length(total);
+                spinner.set_position(done);
+                file
+            }
+            ProgressEvent::Warning(warning) => {
+                spinner.println(format!(
+                    "{} {}",
+                    __MANAGER_STYLE.apply_to("__Manager:"),
+                    WARNING_STYLE.apply_to(format!("warning: {}", warning))
+                ));
+                return;
+            }
+            ProgressEvent::Log(line) => line,
+            ProgressEvent::Finished => return,
+            event => {
+                spinner.set_style(ProgressStyle::default_spinner());
+                event.to_string()
+            }
+        };
+        spinner.set_message(
+            __MANAGER_STYLE
+                .apply_to(format!("__Manager: {}", message))
+                .to_string(),
+        );
+    }
+
     #[for_all_attrs]
     pub fn __manager_abort(&self) {
         self.__manager_finish_with_message("x")
@@ -74,6 +151,7 @@ impl Spinners {
         M: std::fmt::Display,
     {
         if let Some(spinner) = &self.__manager {
+            spinner.set_style(ProgressStyle::default_spinner());
             spinner.finish_with_message(
                 __MANAGER_STYLE
                     .apply_to(format!("__Manager: {}", message))
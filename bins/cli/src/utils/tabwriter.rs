@@ -1,63 +1,105 @@
-use crate::style::*;
-use unipac_managers::managers::*;
+use console::Style;
+use unipac_managers::managers::{PackageDetails, PackageInfo, PlanItem};
 
-#[cfg(feature = "pacman")]
-pub fn pacman_to_string(package: &pacman::Package) -> String {
+pub fn package_to_string<P>(style: &Style, manager: &str, package: &P) -> String
+where
+    P: PackageInfo,
+{
     format!(
-        "{}\t{}\t{}\t{}\n",
-        PACMAN_STYLE.apply_to("Pacman:"),
-        package.database,
-        package.name,
-        package.version
+        "{}\t{}\t{}\t{}\t{}\t{}\n",
+        style.apply_to(format!("{}:", manager)),
+        package.name(),
+        package.identifier().unwrap_or_default(),
+        package.version(),
+        package.origin().unwrap_or_default(),
+        package.description().unwrap_or_default(),
     )
 }
 
-#[cfg(feature = "aur")]
-pub fn aur_to_string(package: &aur::Package) -> String {
+pub fn plan_item_to_string(style: &Style, manager: &str, item: &PlanItem) -> String {
+    let versions = match (&item.old_version, &item.new_version) {
+        (Some(old), Some(new)) => format!("{} -> {}", old, new),
+        (Some(version), None) | (None, Some(version)) => version.clone(),
+        (None, None) => String::new(),
+    };
     format!(
-        "{}\t{}\t{}\n",
-        AUR_STYLE.apply_to("AUR:"),
-        package.name,
-        package.version,
+        "{}\t{}\t{}\t{}\t{}\n",
+        style.apply_to(format!("{}:", manager)),
+        item.action,
+        item.name,
+        versions,
+        item.download_size.map(format_size).unwrap_or_default(),
     )
 }
 
-#[cfg(feature = "flatpak")]
-pub fn flatpak_to_string(package: &flatpak::Package) -> String {
-    format!(
-        "{}\t{}\t{}\t{}\t{}\n",
-        FLATPAK_STYLE.apply_to("Flatpak:"),
-        package.id,
-        package.name,
-        package.version,
-        package.description
-    )
+/// Lists beyond this length are cut, `unipac info --output` giving them whole.
+const MAX_LIST_LENGTH: usize = 10;
+
+fn format_list(list: &[String]) -> Option<String> {
+    if list.is_empty() {
+        return None;
+    }
+    let mut output = list[..list.len().min(MAX_LIST_LENGTH)].join(", ");
+    if list.len() > MAX_LIST_LENGTH {
+        output.push_str(&format!(", ... ({} more)", list.len() - MAX_LIST_LENGTH));
+    }
+    Some(output)
 }
 
-#[cfg(feature = "snap")]
-pub fn snap_to_string(package: &snap::Package) -> String {
-    format!(
-        "{}\t{}\t{}\t{}\t{}\n",
-        SNAP_STYLE.apply_to("Snap:"),
-        package.name,
-        package.version,
-        package.publisher,
-        package.description
-    )
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
 }
 
-#[cfg(feature = "cargo")]
-pub fn cargo_to_string(package: &cargo::Package) -> String {
-    format!(
-        "{}\t{}\t{}\t{}\t{}\n",
-        CARGO_STYLE.apply_to("Cargo:"),
-        package.name,
-        package.version,
-        package
-            .repository
-            .as_ref()
-            .map(|url| url.to_string())
-            .unwrap_or_default(),
-        package.bins.join(", "),
-    )
+/// One `field:\tvalue` line per known field of `details`.
+pub fn details_to_string(details: &PackageDetails) -> String {
+    let fields = [
+        ("Description", details.description.clone()),
+        ("Homepage", details.homepage.clone()),
+        ("Origin", details.origin.clone()),
+        ("Maintainer", details.maintainer.clone()),
+        ("Licenses", format_list(&details.licenses)),
+        ("Dependencies", format_list(&details.dependencies)),
+        ("Installed size", details.installed_size.map(format_size)),
+        (
+            "Install date",
+            details
+                .install_date
+                .map(|date| date.format("%Y-%m-%d %H:%M").to_string()),
+        ),
+        ("Available", format_list(&details.available)),
+    ];
+    let extra = details
+        .extra
+        .iter()
+        .map(|(field, value)| (capitalize(field), Some(value.clone())));
+    let mut output = String::new();
+    for (field, value) in fields
+        .into_iter()
+        .map(|(field, value)| (field.to_string(), value))
+        .chain(extra)
+    {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            output.push_str(&format!("  {}:\t{}\n", field, value));
+        }
+    }
+    output
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
 }
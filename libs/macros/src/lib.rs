@@ -12,7 +12,7 @@ fn replace(input: String, into: (&&str, &&str)) -> String {
 }
 
 fn replace_token(input: TokenTree, into: (&&str, &&str)) -> TokenTree {
-    let output = match input {
+    match input {
         TokenTree::Ident(ident) => {
             TokenTree::Ident(Ident::new(&replace(ident.to_string(), into), ident.span()))
         }
@@ -29,9 +29,7 @@ fn replace_token(input: TokenTree, into: (&&str, &&str)) -> TokenTree {
             TokenTree::Literal(Literal::from_str(&replace(literal.to_string(), into)).unwrap())
         }
         tt => tt,
-    };
-
-    output
+    }
 }
 
 #[proc_macro]
@@ -0,0 +1,189 @@
+use crate::managers::{Action, ErrorKind, PlanItem};
+use crate::utils::dirs::DIRS;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// Something unipac did, as stored in `$XDG_DATA_HOME/unipac/history.jsonl`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: u64,
+    pub timestamp: DateTime<Local>,
+    pub user: String,
+    /// Subcommand that made the transaction, like `install`.
+    pub command: String,
+    /// Transaction an `undo` reverted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<u64>,
+    pub changes: Vec<Change>,
+}
+impl Transaction {
+    pub fn succeeded(&self) -> bool {
+        self.changes.iter().all(Change::succeeded)
+    }
+}
+
+/// One package changed by a [`Transaction`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Change {
+    pub manager: String,
+    pub action: Action,
+    pub package: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// Why the change failed, if it did.
+    pub error: Option<String>,
+}
+impl Change {
+    pub fn new(manager: impl Into<String>, item: PlanItem) -> Self {
+        Self {
+            manager: manager.into(),
+            action: item.action,
+            package: item.name,
+            old_version: item.old_version,
+            new_version: item.new_version,
+            error: None,
+        }
+    }
+
+    pub fn with_error(self, error: Option<String>) -> Self {
+        Self { error, ..self }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// What undoing this change does.
+    pub fn undo(&self) -> PlanItem {
+        let action = match self.action {
+            Action::Install => Action::Remove,
+            Action::Remove => Action::Install,
+            Action::Upgrade => Action::Downgrade,
+            Action::Downgrade => Action::Upgrade,
+        };
+        PlanItem {
+            action,
+            name: self.package.clone(),
+            old_version: self.new_version.clone(),
+            new_version: self.old_version.clone(),
+            download_size: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Fs(std::io::Error),
+    Parse {
+        line: usize,
+        source: serde_json::Error,
+    },
+    Serialize(serde_json::Error),
+}
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Fs(err) => ErrorKind::of_io(err),
+            Self::Parse { .. } | Self::Serialize(_) => ErrorKind::Parse,
+        }
+    }
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fs(err) => write!(f, "Filesystem error: {}", err),
+            Self::Parse { line, source } => {
+                write!(f, "Invalid history entry at line {}: {}", line, source)
+            }
+            Self::Serialize(err) => write!(f, "Failed to serialize history entry: {}", err),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fs(err) => Some(err),
+            Self::Parse { source, .. } => Some(source),
+            Self::Serialize(err) => Some(err),
+        }
+    }
+}
+
+pub fn path() -> Result<PathBuf, Error> {
+    DIRS.place_data_file("history.jsonl").map_err(Error::Fs)
+}
+
+/// Every recorded transaction, oldest first.
+pub fn load() -> Result<Vec<Transaction>, Error> {
+    let file = match std::fs::File::open(path()?) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(Error::Fs(err)),
+    };
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.map_err(Error::Fs)?;
+            serde_json::from_str(&line).map_err(|source| Error::Parse {
+                line: i + 1,
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Latest transaction that is neither an undo nor undone already.
+pub fn last_undoable(transactions: Vec<Transaction>) -> Option<Transaction> {
+    let undone = transactions
+        .iter()
+        .filter_map(|transaction| transaction.undoes)
+        .collect::<Vec<_>>();
+    transactions
+        .into_iter()
+        .rev()
+        .find(|transaction| transaction.undoes.is_none() && !undone.contains(&transaction.id))
+}
+
+/// Appends a transaction made of `changes` to the history.
+pub fn record(command: &str, changes: Vec<Change>) -> Result<Transaction, Error> {
+    append(command, None, changes)
+}
+
+/// Appends the undo of transaction `id`, made of `changes`, to the history.
+pub fn record_undo(id: u64, changes: Vec<Change>) -> Result<Transaction, Error> {
+    append("undo", Some(id), changes)
+}
+
+fn append(command: &str, undoes: Option<u64>, changes: Vec<Change>) -> Result<Transaction, Error> {
+    let id = load()?.last().map_or(1, |transaction| transaction.id + 1);
+    let transaction = Transaction {
+        id,
+        timestamp: Local::now(),
+        user: user(),
+        command: command.into(),
+        undoes,
+        changes,
+    };
+    let mut line = serde_json::to_string(&transaction).map_err(Error::Serialize)?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path()?)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(Error::Fs)?;
+    Ok(transaction)
+}
+
+/// User who ran unipac, before it elevated itself with sudo.
+fn user() -> String {
+    std::env::var("SUDO_USER")
+        .or_else(|_| std::env::var("USER"))
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_else(|_| "unknown".into())
+}
@@ -1,2 +1,5 @@
+pub mod history;
 pub mod managers;
+pub mod manifest;
+pub mod pins;
 pub mod utils;
@@ -1,10 +1,25 @@
-use crate::utils::alpm::Alpm;
+use super::{
+    report, Action, CacheUsage, ErrorKind, Exports, ManagerError, PackageDetails, PackageInfo,
+    Phase, ProgressEvent,
+};
+use crate::history::Change;
+use crate::pins;
+use crate::utils::alpm::{
+    find_archive, find_owner, install_file, orphans, package_details, package_exports,
+    remove_packages, stale_archives, Alpm, TransactionError,
+};
+use crate::utils::cache::{self, CacheFiles};
+use crate::utils::command::{self, CommandError};
+use crate::utils::config;
 use crate::utils::dirs::{get_aur_extracted_path, DIRS};
 use alpm_utils::DbListExt as _;
 use raur::Raur;
+use serde::Deserialize;
+use std::borrow::Cow;
 use std::fmt::Display;
 use std::os::unix::process::CommandExt;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::Arc;
 use tempfile::tempdir;
 use tokio::sync::mpsc::Sender;
@@ -12,12 +27,18 @@ use tokio::sync::mpsc::Sender;
 pub struct Package {
     pub name: String,
     pub version: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub installed_size: Option<u64>,
 }
 impl From<raur::Package> for Package {
     fn from(package: raur::Package) -> Self {
         Self {
             name: package.name,
             version: package.version,
+            description: package.description,
+            url: package.url,
+            installed_size: None,
         }
     }
 }
@@ -26,65 +47,194 @@ impl From<&alpm::Package> for Package {
         Self {
             name: package.name().into(),
             version: package.version().to_string(),
+            description: package.desc().map(|desc| desc.into()),
+            url: package.url().map(|url| url.into()),
+            installed_size: u64::try_from(package.isize()).ok(),
         }
     }
 }
+impl PackageInfo for Package {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn version(&self) -> &str {
+        &self.version
+    }
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    fn homepage(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        Some("aur".into())
+    }
+    fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
-    Alpm(Option<alpm::Error>),
-    Raur(Option<raur::Error>),
-    Fs(Option<std::io::Error>),
-    Command(&'static str, Option<std::io::Error>),
+    Alpm(alpm::Error),
+    Raur(raur::Error),
+    Fs(std::io::Error),
+    Command(CommandError),
+    Pins(pins::Error),
+    Transaction(TransactionError),
+    NotFound(String),
+    UnknownUser(String),
 }
 impl From<raur::Error> for Error {
     fn from(error: raur::Error) -> Self {
-        Self::Raur(Some(error))
+        Self::Raur(error)
     }
 }
 impl From<alpm::Error> for Error {
     fn from(error: alpm::Error) -> Self {
-        Self::Alpm(Some(error))
+        Self::Alpm(error)
+    }
+}
+impl From<TransactionError> for Error {
+    fn from(error: TransactionError) -> Self {
+        Self::Transaction(error)
     }
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Alpm(Some(err)) => write!(f, "Alpm: {}", err),
-            Self::Alpm(None) => write!(f, "Alpm: Unknown error"),
-            Self::Raur(Some(err)) => write!(f, "Raur: {}", err),
-            Self::Raur(None) => write!(f, "Raur: Unknown error"),
-            Self::Fs(Some(err)) => write!(f, "Fs: {}", err),
-            Self::Fs(None) => write!(f, "Fs: Unknown error"),
-            Self::Command(command, Some(err)) => write!(f, "Command \"{}\": {}", command, err),
-            Self::Command(command, None) => write!(f, "Command \"{}\": Unknown error", command),
+            Self::Alpm(err) => write!(f, "Alpm: {}", err),
+            Self::Raur(err) => write!(f, "Raur: {}", err),
+            Self::Fs(err) => write!(f, "Fs: {}", err),
+            Self::Command(err) => write!(f, "Command error: {}", err),
+            Self::Pins(err) => write!(f, "Pins: {}", err),
+            Self::Transaction(err) => write!(f, "Transaction failed: {}", err),
+            Self::NotFound(name) => write!(f, "Package {} not found in the AUR", name),
+            Self::UnknownUser(name) => write!(f, "Unknown build user {}", name),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Alpm(err) => Some(err),
+            Self::Raur(err) => Some(err),
+            Self::Fs(err) => Some(err),
+            Self::Command(err) => Some(err),
+            Self::Pins(err) => Some(err),
+            Self::Transaction(err) => Some(err),
+            Self::NotFound(_) | Self::UnknownUser(_) => None,
+        }
+    }
+}
+impl ManagerError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Alpm(err) => crate::utils::alpm::error_kind(err),
+            Self::Raur(raur::Error::Reqwest(err)) if err.is_decode() => ErrorKind::Parse,
+            Self::Raur(raur::Error::Reqwest(_)) => ErrorKind::Network,
+            Self::Raur(raur::Error::Aur(_)) => ErrorKind::Other,
+            Self::Fs(err) => ErrorKind::of_io(err),
+            Self::Command(err) => crate::utils::alpm::command_error_kind(err),
+            Self::Pins(err) => err.kind(),
+            Self::Transaction(err) => err.kind(),
+            Self::NotFound(_) | Self::UnknownUser(_) => ErrorKind::NotFound,
+        }
+    }
+}
+
+/// User `makepkg` runs as, since it refuses to run as root.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum BuildUser {
+    Id(u32),
+    Name(String),
+}
+impl BuildUser {
+    fn uid(&self) -> Result<u32, Error> {
+        match self {
+            Self::Id(uid) => Ok(*uid),
+            Self::Name(name) => command::output(Command::new("id").args(["-u", name]))
+                .ok()
+                .and_then(|uid| uid.trim().parse().ok())
+                .ok_or_else(|| Error::UnknownUser(name.clone())),
         }
     }
 }
+impl Default for BuildUser {
+    fn default() -> Self {
+        Self::Id(1000)
+    }
+}
+
+/// `[aur]` table of the configuration file.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    pub build_user: BuildUser,
+}
 
 pub struct AUR {
     alpm: Arc<Alpm>,
     raur: raur::Handle,
-    progress_sender: Option<Sender<String>>,
+    progress_sender: Option<Sender<ProgressEvent>>,
+    options: Options,
 }
 impl AUR {
     pub fn new() -> Self {
         Self::create(None)
     }
-    pub fn with_progress(progress_sender: Sender<String>) -> Self {
-        Self::create(Some(progress_sender.clone()))
+    pub fn with_progress(progress_sender: Sender<ProgressEvent>) -> Self {
+        Self::create(Some(progress_sender))
     }
 
-    fn create(progress_sender: Option<Sender<String>>) -> Self {
+    fn create(progress_sender: Option<Sender<ProgressEvent>>) -> Self {
         let alpm = Arc::new(Alpm::new());
         Self {
             alpm,
             raur: raur::Handle::new(),
             progress_sender,
+            options: config::section("aur"),
+        }
+    }
+
+    /// Build directories of the `aur` cache directory, all of it being stale
+    /// once its package is uninstalled.
+    fn cache_files(&self, keep: usize) -> Result<CacheFiles, Error> {
+        let dir = DIRS.create_cache_directory("aur").map_err(Error::Fs)?;
+        let entries = cache::entries(&dir);
+        let alpm = self.alpm.lock();
+        let localdb = alpm.localdb();
+        let mut stale = vec![];
+        for entry in &entries {
+            let installed = entry
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| localdb.pkg(name).is_ok());
+            if !installed {
+                stale.push(entry.clone());
+                continue;
+            }
+            // Sources extracted by makepkg and the tree it packaged
+            stale.extend(
+                ["src", "pkg"]
+                    .map(|name| entry.join(name))
+                    .into_iter()
+                    .filter(|path| path.exists()),
+            );
+            stale.extend(stale_archives([entry.as_path()], keep));
         }
+        Ok(CacheFiles { entries, stale })
+    }
+}
+impl Default for AUR {
+    fn default() -> Self {
+        Self::new()
     }
 }
 impl super::Manager for AUR {
+    const NAME: &'static str = "aur";
+
     type Package = Package;
     type Error = Error;
 
@@ -95,7 +245,22 @@ impl super::Manager for AUR {
         let packages = localdb
             .pkgs()
             .iter()
-            .filter_map(|pkg| (!syncdbs.pkg(pkg.name()).is_ok()).then(|| Package::from(pkg)))
+            .filter(|pkg| syncdbs.pkg(pkg.name()).is_err())
+            .map(Package::from)
+            .collect();
+        Ok(packages)
+    }
+
+    async fn list_explicit(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        let alpm = self.alpm.lock();
+        let syncdbs = alpm.syncdbs();
+        let localdb = alpm.localdb();
+        let packages = localdb
+            .pkgs()
+            .iter()
+            .filter(|pkg| pkg.reason() == alpm::PackageReason::Explicit)
+            .filter(|pkg| syncdbs.pkg(pkg.name()).is_err())
+            .map(Package::from)
             .collect();
         Ok(packages)
     }
@@ -104,7 +269,7 @@ impl super::Manager for AUR {
         let alpm = self.alpm.lock();
         let localdb = alpm.localdb();
         let syncdbs = alpm.syncdbs();
-        let package = localdb.pkg(name).map(|pkg| Package::from(pkg));
+        let package = localdb.pkg(name).map(Package::from);
         let Ok(package) = package else {
             return Ok(None);
         };
@@ -134,6 +299,74 @@ impl super::Manager for AUR {
         Ok(pkg)
     }
 
+    /// Details of the local package when installed, completed by the AUR.
+    async fn info(&self, name: &str) -> Result<Option<PackageDetails>, Self::Error> {
+        let installed = {
+            let alpm = self.alpm.lock();
+            if alpm.syncdbs().pkg(name).is_ok() {
+                return Ok(None);
+            }
+            alpm.localdb()
+                .pkg(name)
+                .ok()
+                .map(|pkg| package_details(pkg, true))
+        };
+        let aur_pkg = self.raur.info(&[name]).await?.into_iter().next();
+        let mut details = match (installed, &aur_pkg) {
+            (Some(details), _) => details,
+            (None, Some(pkg)) => PackageDetails {
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+                description: pkg.description.clone(),
+                homepage: pkg.url.clone(),
+                licenses: pkg.license.clone(),
+                dependencies: pkg.depends.clone(),
+                ..PackageDetails::default()
+            },
+            (None, None) => return Ok(None),
+        };
+        details.origin = Some("aur".into());
+        if let Some(pkg) = aur_pkg {
+            details.maintainer = pkg.maintainer;
+            details.available = vec![pkg.version];
+        }
+        Ok(Some(details))
+    }
+
+    async fn list_orphans(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        let alpm = self.alpm.lock();
+        let syncdbs = alpm.syncdbs();
+        let packages = orphans(&alpm)
+            .into_iter()
+            .filter(|pkg| syncdbs.pkg(pkg.name()).is_err())
+            .map(Package::from)
+            .collect();
+        Ok(packages)
+    }
+
+    async fn exports(&self, package: &Self::Package) -> Result<Exports, Self::Error> {
+        let alpm = self.alpm.lock();
+        let pkg = alpm.localdb().pkg(package.name.as_str())?;
+        Ok(package_exports(pkg))
+    }
+
+    async fn owns(&self, path: &Path) -> Result<Option<Self::Package>, Self::Error> {
+        let alpm = self.alpm.lock();
+        let Some(pkg) = find_owner(&alpm, path) else {
+            return Ok(None);
+        };
+        Ok(alpm.syncdbs().pkg(pkg.name()).is_err().then(|| pkg.into()))
+    }
+
+    /// Sources and built packages left in the `aur` cache directory.
+    async fn cache_usage(&self, keep: usize) -> Result<CacheUsage, Self::Error> {
+        Ok(self.cache_files(keep)?.usage())
+    }
+
+    async fn clean_cache(&self, keep: usize) -> Result<u64, Self::Error> {
+        self.cache_files(keep)?.clean().map_err(Error::Fs)
+    }
+
     async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
         let version = &self
             .raur
@@ -141,48 +374,49 @@ impl super::Manager for AUR {
             .await?
             .into_iter()
             .next()
-            .ok_or(Error::Raur(None))?
+            .ok_or_else(|| Error::NotFound(package.name.clone()))?
             .version;
 
-        let path = get_aur_extracted_path(&package.name).map_err(|err| Error::Fs(err.into()))?;
-        println!("Extracted path: {}", path.display());
-        Command::new("makepkg")
-            .uid(1000)
-            .current_dir(path.clone())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::piped())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|err| Error::Command("makepkg", err.into()))?;
+        let path = get_aur_extracted_path(&package.name).map_err(Error::Fs)?;
+        report(&self.progress_sender, ProgressEvent::Phase(Phase::Building)).await;
+        // makepkg prints its own messages on stderr, the build output going to stdout
+        let mut stream = command::stream_stderr(
+            Command::new("makepkg")
+                .arg("--nocolor")
+                .uid(self.options.build_user.uid()?)
+                .current_dir(path.clone()),
+        )
+        .map_err(Error::Command)?;
+        for line in stream.by_ref() {
+            let event = match line.strip_prefix("==> WARNING:") {
+                Some(warning) => ProgressEvent::Warning(warning.trim().into()),
+                None => ProgressEvent::Log(line.trim_start_matches("==> ").into()),
+            };
+            report(&self.progress_sender, event).await;
+        }
+        stream.wait().map_err(Error::Command)?;
 
+        let filepath = path.join(format!(
+            "{}-{}-{}.tar.zst",
+            package.name,
+            version,
+            std::env::consts::ARCH
+        ));
+        report(
+            &self.progress_sender,
+            ProgressEvent::Phase(Phase::Installing),
+        )
+        .await;
         let mut alpm = self.alpm.lock();
-        alpm.trans_init(alpm::TransFlag::NONE)?;
-        let result = {
-            let filepath = path.join(format!(
-                "{}-{}-{}.tar.zst",
-                package.name,
-                version,
-                std::env::consts::ARCH
-            ));
-            let filename = filepath.to_str().ok_or(Error::Fs(None))?;
-            let pkg = alpm.pkg_load(filename, true, alpm.local_file_siglevel())?;
-            alpm.trans_add_pkg(pkg).map_err(|err| err.error)?;
-            alpm.trans_prepare().map_err(|err| err.error())?;
-            alpm.trans_commit().map_err(|err| err.error())?;
-            Ok(())
-        };
-        alpm.trans_release()?;
-        result
+        install_file(&mut alpm, &filepath)?;
+        Ok(())
     }
 
     async fn uninstall(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("pacman")
-            .args(["--noconfirm", "-R", package.name.as_str()])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Alpm(None))?;
+        {
+            let mut alpm = self.alpm.lock();
+            remove_packages(&mut alpm, [package.name.as_str()])?;
+        }
 
         let Ok(dir) = DIRS.create_cache_directory("aur") else {
             eprintln!("Failed to create cache directory");
@@ -197,41 +431,54 @@ impl super::Manager for AUR {
         Ok(())
     }
 
+    /// Installs back the previously built package, left by makepkg in the
+    /// `aur` cache directory or copied to the pacman cache.
+    async fn revert(&self, change: &Change) -> Result<bool, Self::Error> {
+        let version = match (change.action, &change.old_version) {
+            (Action::Install, _) | (_, None) => return super::revert(self, change).await,
+            (_, Some(version)) => version,
+        };
+        let mut dirs = vec![get_aur_extracted_path(&change.package).map_err(Error::Fs)?];
+        let mut alpm = self.alpm.lock();
+        dirs.extend(alpm.cachedirs().iter().map(PathBuf::from));
+        let archive = find_archive(dirs.iter().map(PathBuf::as_path), &change.package, version);
+        let Some(archive) = archive else {
+            return Ok(false);
+        };
+        install_file(&mut alpm, &archive)?;
+        Ok(true)
+    }
+
     async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error> {
         let alpm = self.alpm.lock();
-        let tmp_dir = tempdir().map_err(|err| Error::Fs(err.into()))?;
+        let tmp_dir = tempdir().map_err(Error::Fs)?;
         let tmp_path = &tmp_dir.path();
         let tmp_path_str = tmp_path.to_str().expect("Invalid path");
 
         std::os::unix::fs::symlink("/var/lib/pacman/local", tmp_path.join("local"))
-            .map_err(|err| Error::Fs(err.into()))?;
-        let status = Command::new("fakeroot")
-            .args([
-                "--",
-                "pacman",
-                "-Sy",
-                "--dbpath",
-                tmp_path_str,
-                "--logfile",
-                "/dev/null",
-                "--noconfirm",
-            ])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()
-            .map_err(|_| Error::Alpm(None))?;
-
-        if !status.success() {
-            return Err(Error::Alpm(None));
-        }
+            .map_err(Error::Fs)?;
+        command::run(Command::new("fakeroot").args([
+            "--",
+            "pacman",
+            "-Sy",
+            "--dbpath",
+            tmp_path_str,
+            "--logfile",
+            "/dev/null",
+            "--noconfirm",
+        ]))
+        .map_err(Error::Command)?;
 
-        let output = Command::new("pacman")
-            .args(["-Qum", "--dbpath", tmp_path_str])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
-            .map_err(|_| Error::Alpm(None))?;
+        // pacman exits with 1 when there is nothing to upgrade
+        let output = match command::output(Command::new("pacman").args([
+            "-Qum",
+            "--dbpath",
+            tmp_path_str,
+        ])) {
+            Ok(output) => output,
+            Err(err) if err.code() == Some(1) => String::new(),
+            Err(err) => return Err(Error::Command(err)),
+        };
 
         let packages = output
             .lines()
@@ -247,7 +494,7 @@ impl super::Manager for AUR {
             })
             .collect();
 
-        Ok(packages)
+        pins::filter_updates(Self::NAME, packages).map_err(Error::Pins)
     }
 
     async fn count_updates(&self) -> Result<usize, Self::Error> {
@@ -256,17 +503,26 @@ impl super::Manager for AUR {
 
     async fn update(&self) -> Result<(), Self::Error> {
         let updates = self.list_updates().await?;
+        let total = updates.len();
+        report(
+            &self.progress_sender,
+            ProgressEvent::Started { total: Some(total) },
+        )
+        .await;
+        let mut result = Ok(());
         for (i, package) in updates.iter().enumerate() {
-            if let Some(progress_sender) = &self.progress_sender {
-                let _ = progress_sender
-                    .send(format!("{}% {}", i * 100 / updates.len(), package.name))
-                    .await;
+            let step = ProgressEvent::Step {
+                step: i + 1,
+                total,
+                package: package.name.clone(),
+            };
+            report(&self.progress_sender, step).await;
+            result = self.install(package).await;
+            if result.is_err() {
+                break;
             }
-            self.install(package).await?;
-        }
-        if let Some(progress_sender) = &self.progress_sender {
-            let _ = progress_sender.send("100%".into()).await;
         }
-        Ok(())
+        report(&self.progress_sender, ProgressEvent::Finished).await;
+        result
     }
 }
@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+/// Disk space a manager's caches take, as shown by `unipac cache stats`.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct CacheUsage {
+    /// Size of the caches in bytes.
+    pub size: u64,
+    /// Bytes cleaning the caches would free.
+    pub reclaimable: u64,
+}
@@ -1,17 +1,81 @@
-use super::Manager;
+use super::{
+    report, Action, CacheUsage, ErrorKind, Exports, Manager, ManagerError, PackageDetails,
+    PackageInfo, ProgressEvent,
+};
+use crate::history::Change;
+use crate::manifest::Entry;
+use crate::pins;
+use crate::utils::cache::{self, CacheFiles};
+use crate::utils::command::{self, CommandError};
+use crate::utils::config;
+use chrono::DateTime;
 use reqwest::Url;
 use semver::Version;
 use serde::Deserialize;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::future::Future;
-use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::str::FromStr;
 use std::sync::RwLock;
 use tokio::sync::mpsc::Sender;
 
-pub type Error = String;
+#[derive(Debug)]
+pub enum Error {
+    NoHomeDirectory,
+    Config(std::io::Error),
+    Fs(std::io::Error),
+    ConfigFormat(serde_json::Error),
+    Parse { line: String },
+    Network(reqwest::Error),
+    Http(reqwest::StatusCode),
+    Command(CommandError),
+    Pins(pins::Error),
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoHomeDirectory => write!(f, "Failed to get home directory"),
+            Self::Config(err) => write!(f, "Failed to open config file: {}", err),
+            Self::Fs(err) => write!(f, "File system: {}", err),
+            Self::ConfigFormat(err) => write!(f, "Failed to parse config: {}", err),
+            Self::Parse { line } => write!(f, "Failed to parse \"{}\"", line),
+            Self::Network(err) => write!(f, "Failed to send request: {}", err),
+            Self::Http(status) => write!(f, "Request failed: {}", status),
+            Self::Command(err) => write!(f, "Command error: {}", err),
+            Self::Pins(err) => write!(f, "Pins: {}", err),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Config(err) => Some(err),
+            Self::Fs(err) => Some(err),
+            Self::ConfigFormat(err) => Some(err),
+            Self::Network(err) => Some(err),
+            Self::Command(err) => Some(err),
+            Self::Pins(err) => Some(err),
+            Self::NoHomeDirectory | Self::Parse { .. } | Self::Http(_) => None,
+        }
+    }
+}
+impl ManagerError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::NoHomeDirectory => ErrorKind::NotFound,
+            Self::Config(err) | Self::Fs(err) => ErrorKind::of_io(err),
+            Self::ConfigFormat(_) | Self::Parse { .. } => ErrorKind::Parse,
+            Self::Network(err) if err.is_decode() => ErrorKind::Parse,
+            Self::Network(_) => ErrorKind::Network,
+            Self::Http(reqwest::StatusCode::NOT_FOUND) => ErrorKind::NotFound,
+            Self::Http(_) => ErrorKind::Network,
+            Self::Command(err) => err.kind(),
+            Self::Pins(err) => err.kind(),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub enum PackageRepository {
@@ -21,23 +85,20 @@ pub enum PackageRepository {
 impl FromStr for PackageRepository {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (kind, url) = s
-            .split_once("+")
-            .ok_or("Failed to parse package repository")?;
+        let parse_error = || Error::Parse { line: s.into() };
+        let (kind, url) = s.split_once("+").ok_or_else(parse_error)?;
         match kind {
             "git" => {
-                let (url, commit) = url
-                    .split_once("#")
-                    .ok_or("Failed to parse package repository")?;
+                let (url, commit) = url.split_once("#").ok_or_else(parse_error)?;
                 Ok(Self::Git {
-                    url: Url::parse(url).map_err(|_| "Failed to parse package repository")?,
+                    url: Url::parse(url).map_err(|_| parse_error())?,
                     commit: commit.into(),
                 })
             }
             "registry" => Ok(Self::Registry {
-                url: Url::parse(url).map_err(|_| "Failed to parse package repository")?,
+                url: Url::parse(url).map_err(|_| parse_error())?,
             }),
-            _ => Err("Failed to parse package repository".into()),
+            _ => Err(parse_error()),
         }
     }
 }
@@ -54,35 +115,63 @@ impl Display for PackageRepository {
 pub struct Package {
     pub name: String,
     pub version: String,
+    pub description: Option<String>,
     pub repository: Option<PackageRepository>,
     pub bins: Vec<String>,
+    /// Features the crate is built with.
+    pub features: Vec<String>,
 }
 impl From<GetCratesResponseCrate> for Package {
     fn from(crate_: GetCratesResponseCrate) -> Self {
         Self {
             name: crate_.name,
             version: crate_.max_stable_version,
+            description: crate_.description,
             repository: crate_.repository.parse().ok(),
             bins: vec![],
+            features: vec![],
         }
     }
 }
 impl Package {
     fn try_from_config_install(key: String, data: ConfigInstall) -> Result<Self, Error> {
+        let parse_error = || Error::Parse { line: key.clone() };
         let mut parts = key.split(" ");
-        let name = parts.next().ok_or("Failed to parse package name")?;
-        let version = parts.next().ok_or("Failed to parse package version")?;
-        let repository = parts.next().ok_or("Failed to parse package repository")?;
+        let name = parts.next().ok_or_else(parse_error)?;
+        let version = parts.next().ok_or_else(parse_error)?;
+        let repository = parts.next().ok_or_else(parse_error)?;
         let repository = &repository[1..repository.len() - 1];
         Ok(Package {
             name: name.into(),
             version: version.into(),
+            description: None,
             repository: repository.parse().ok(),
             bins: data.bins,
+            features: data.features,
         })
     }
 }
 
+impl PackageInfo for Package {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn version(&self) -> &str {
+        &self.version
+    }
+    fn identifier(&self) -> Option<Cow<'_, str>> {
+        (!self.bins.is_empty()).then(|| self.bins.join(", ").into())
+    }
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        self.repository
+            .as_ref()
+            .map(|repository| repository.to_string().into())
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize)]
 struct ConfigInstall {
@@ -118,6 +207,43 @@ struct GetCratesResponse {
     crates: Vec<GetCratesResponseCrate>,
 }
 
+#[derive(Deserialize)]
+struct GetCrateResponseCrate {
+    description: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
+    max_stable_version: Option<String>,
+}
+#[derive(Deserialize)]
+struct GetCrateResponseUser {
+    login: String,
+}
+#[derive(Deserialize)]
+struct GetCrateResponseVersion {
+    num: String,
+    license: Option<String>,
+    yanked: bool,
+    published_by: Option<GetCrateResponseUser>,
+}
+#[derive(Deserialize)]
+struct GetCrateResponse {
+    #[serde(rename = "crate")]
+    crate_: GetCrateResponseCrate,
+    versions: Vec<GetCrateResponseVersion>,
+}
+
+#[derive(Deserialize)]
+struct GetDependenciesResponseDependency {
+    crate_id: String,
+    req: String,
+    kind: String,
+    optional: bool,
+}
+#[derive(Deserialize)]
+struct GetDependenciesResponse {
+    dependencies: Vec<GetDependenciesResponseDependency>,
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize)]
 struct CrateIndexElement {
@@ -134,8 +260,26 @@ struct CommitResponse {
     sha: String,
 }
 
+/// `[cargo]` table of the configuration file.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Extra arguments given to `cargo install`, like `--locked`.
+    pub install_args: Vec<String>,
+}
+
+/// Name and version of a `.crate` archive stem, like `serde-1.0.197`, both
+/// of which may contain dashes.
+fn parse_crate_file_stem(stem: &str) -> Option<(&str, Version)> {
+    stem.match_indices('-').find_map(|(index, _)| {
+        let version = Version::parse(&stem[index + 1..]).ok()?;
+        Some((&stem[..index], version))
+    })
+}
+
 pub struct Cargo {
-    progress_sender: Option<Sender<String>>,
+    progress_sender: Option<Sender<ProgressEvent>>,
+    options: Options,
     http_client: reqwest::Client,
     update_cache: RwLock<Option<Vec<Package>>>,
 }
@@ -143,75 +287,153 @@ impl Cargo {
     pub fn new() -> Self {
         Self::create(None)
     }
-    pub fn with_progress(progress_sender: Sender<String>) -> Self {
+    pub fn with_progress(progress_sender: Sender<ProgressEvent>) -> Self {
         Self::create(Some(progress_sender))
     }
-    fn create(progress_sender: Option<Sender<String>>) -> Self {
+    fn create(progress_sender: Option<Sender<ProgressEvent>>) -> Self {
         let http_client = reqwest::Client::builder()
             .user_agent("Unipac <https://github.com/polnio/unipac>")
             .build()
             .expect("Failed to create HTTP client");
         Self {
             progress_sender,
+            options: config::section("cargo"),
             http_client,
             update_cache: RwLock::new(None),
         }
     }
 
     fn config(&self) -> Result<Config, Error> {
-        let home_dir = dirs::home_dir().ok_or("Failed to get home directory")?;
+        let home_dir = dirs::home_dir().ok_or(Error::NoHomeDirectory)?;
         let config_path = home_dir.join(".cargo/.crates2.json");
-        let file = std::fs::File::open(config_path).map_err(|_| "Failed to open config file")?;
-        let config: Config = serde_json::from_reader(file).map_err(|_| "Failed to parse config")?;
+        let file = std::fs::File::open(config_path).map_err(Error::Config)?;
+        let config: Config = serde_json::from_reader(file).map_err(Error::ConfigFormat)?;
         Ok(config)
     }
 
-    async fn inner_install<F: Future, C: Fn(String) -> F>(
-        &self,
-        package: &Package,
-        progress_callback: C,
-    ) -> Result<(), Error> {
-        let stdout = Command::new("cargo")
-            .args(["install", &package.name, "--version", &package.version])
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| format!("Failed to install package: {}", err))?
-            .stderr
-            .ok_or("Failed to install package")?;
-
-        for line in BufReader::new(stdout).lines() {
-            let Ok(line) = line else {
-                continue;
-            };
-            let mut parts = line.split(' ');
-            while let Some(part) = parts.next() {
-                if part == "Compiling" {
-                    break;
-                }
-            }
-            let Some(name) = parts.next() else {
+    /// Registry archives and extracted sources under `~/.cargo`, both keeping
+    /// the `keep` newest versions of every crate.
+    fn cache_files(&self, keep: usize) -> Result<CacheFiles, Error> {
+        let home_dir = dirs::home_dir().ok_or(Error::NoHomeDirectory)?;
+        let registry_dir = home_dir.join(".cargo/registry");
+        let index_entries = |dir: &str| {
+            cache::entries(&registry_dir.join(dir))
+                .iter()
+                .flat_map(|index| cache::entries(index))
+                .collect::<Vec<_>>()
+        };
+        let stale_versions = |paths: &[PathBuf], suffix: &str| {
+            let versions = paths.iter().filter_map(|path| {
+                let stem = path.file_name()?.to_str()?.strip_suffix(suffix)?;
+                let (name, version) = parse_crate_file_stem(stem)?;
+                // Crates of different registries are not versions of one another
+                let name = path.with_file_name(name).to_string_lossy().into_owned();
+                Some((name, version, path.clone()))
+            });
+            cache::stale_versions(versions, keep, |a, b| a.cmp(b))
+        };
+        let sources = index_entries("src");
+        let archives = index_entries("cache");
+        let mut stale = stale_versions(&archives, ".crate");
+        stale.extend(stale_versions(&sources, ""));
+        Ok(CacheFiles {
+            entries: sources.into_iter().chain(archives).collect(),
+            stale,
+        })
+    }
+
+    /// Crates.io page of `name`, if there is one.
+    async fn get_crate(&self, name: &str) -> Result<Option<GetCrateResponse>, Error> {
+        let response = self
+            .http_client
+            .get(format!("https://crates.io/api/v1/crates/{}", name))
+            .send()
+            .await
+            .map_err(Error::Network)?;
+
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => response.json().await.map_err(Error::Network),
+            status => Err(Error::Http(status)),
+        }
+    }
+
+    /// Non-optional dependencies of `name` at `version`, with their requirement.
+    async fn dependencies(&self, name: &str, version: &str) -> Result<Vec<String>, Error> {
+        let response = self
+            .http_client
+            .get(format!(
+                "https://crates.io/api/v1/crates/{}/{}/dependencies",
+                name, version
+            ))
+            .send()
+            .await
+            .map_err(Error::Network)?;
+
+        if !response.status().is_success() {
+            return Err(Error::Http(response.status()));
+        }
+
+        let json: GetDependenciesResponse = response.json().await.map_err(Error::Network)?;
+
+        let dependencies = json
+            .dependencies
+            .into_iter()
+            .filter(|dependency| dependency.kind == "normal" && !dependency.optional)
+            .map(|dependency| format!("{} {}", dependency.crate_id, dependency.req))
+            .collect();
+
+        Ok(dependencies)
+    }
+
+    /// Runs `cargo install`, reporting the crates it compiles and the warnings it prints.
+    async fn inner_install(&self, package: &Package) -> Result<(), Error> {
+        let mut stream = command::stream_stderr(
+            Command::new("cargo")
+                .args(["install", &package.name, "--version", &package.version])
+                .args(
+                    (!package.features.is_empty())
+                        .then(|| ["--features".into(), package.features.join(",")])
+                        .into_iter()
+                        .flatten(),
+                )
+                .args(&self.options.install_args),
+        )
+        .map_err(Error::Command)?;
+
+        for line in stream.by_ref() {
+            let line = line.trim();
+            let event = if let Some(warning) = line.strip_prefix("warning:") {
+                ProgressEvent::Warning(warning.trim().into())
+            } else if line.starts_with("Compiling ") {
+                ProgressEvent::Log(line.into())
+            } else {
                 continue;
             };
-            progress_callback(name.into()).await;
+            report(&self.progress_sender, event).await;
         }
 
-        Ok(())
+        stream.wait().map_err(Error::Command)
+    }
+}
+impl Default for Cargo {
+    fn default() -> Self {
+        Self::new()
     }
 }
 impl Manager for Cargo {
+    const NAME: &'static str = "cargo";
+
     type Package = Package;
     type Error = Error;
 
     async fn list(&self) -> Result<Vec<Self::Package>, Self::Error> {
         let config = self.config()?;
-        let packages = config
+        config
             .installs
             .into_iter()
             .map(|(key, data)| Package::try_from_config_install(key, data))
-            .collect();
-
-        packages
+            .collect()
     }
 
     async fn find(&self, name: &str) -> Result<Option<Self::Package>, Self::Error> {
@@ -231,16 +453,13 @@ impl Manager for Cargo {
             .query(&[("q", query)])
             .send()
             .await
-            .map_err(|err| format!("Failed to send request: {err}"))?;
+            .map_err(Error::Network)?;
 
         if !response.status().is_success() {
-            return Err("Failed to send request".into());
+            return Err(Error::Http(response.status()));
         }
 
-        let json: GetCratesResponse = response
-            .json()
-            .await
-            .map_err(|_| "Failed to parse response")?;
+        let json: GetCratesResponse = response.json().await.map_err(Error::Network)?;
 
         let packages = json.crates.into_iter().map(Package::from).collect();
 
@@ -254,16 +473,13 @@ impl Manager for Cargo {
             .query(&[("q", query)])
             .send()
             .await
-            .map_err(|err| format!("Failed to send request: {err}"))?;
+            .map_err(Error::Network)?;
 
         if !response.status().is_success() {
-            return Err("Failed to send request".into());
+            return Err(Error::Http(response.status()));
         }
 
-        let json: GetCratesResponse = response
-            .json()
-            .await
-            .map_err(|_| "Failed to parse response")?;
+        let json: GetCratesResponse = response.json().await.map_err(Error::Network)?;
 
         let packages = json
             .crates
@@ -274,25 +490,178 @@ impl Manager for Cargo {
         Ok(packages)
     }
 
-    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        self.inner_install(package, |name| async move {
-            let Some(progress_sender) = &self.progress_sender.clone() else {
-                return;
+    /// Details from crates.io, with how the crate is built when installed.
+    async fn info(&self, name: &str) -> Result<Option<PackageDetails>, Self::Error> {
+        let installed = self
+            .config()?
+            .installs
+            .into_iter()
+            .find(|(key, _)| key.split(' ').next() == Some(name));
+        let crate_ = self.get_crate(name).await?;
+        if installed.is_none() && crate_.is_none() {
+            return Ok(None);
+        }
+
+        let mut details = PackageDetails {
+            name: name.into(),
+            origin: Some("crates.io".into()),
+            ..PackageDetails::default()
+        };
+        if let Some((key, install)) = installed {
+            let mut parts = key.split(' ');
+            details.installed = true;
+            details.version = parts.nth(1).unwrap_or_default().into();
+            details.origin = parts
+                .next()
+                .map(|source| source.trim_matches(['(', ')']).into());
+
+            let home_dir = dirs::home_dir().ok_or(Error::NoHomeDirectory)?;
+            let bins = install
+                .bins
+                .iter()
+                .filter_map(|bin| std::fs::metadata(home_dir.join(".cargo/bin").join(bin)).ok())
+                .collect::<Vec<_>>();
+            details.installed_size = Some(bins.iter().map(|bin| bin.len()).sum());
+            details.install_date = bins
+                .iter()
+                .filter_map(|bin| bin.modified().ok())
+                .max()
+                .map(DateTime::from);
+
+            let features = if install.all_features {
+                "all".into()
+            } else {
+                install.features.join(", ")
             };
-            let _ = progress_sender.send(format!("{}", name)).await;
+            if !features.is_empty() {
+                details.extra.insert("features".into(), features);
+            }
+            if install.no_default_features {
+                details
+                    .extra
+                    .insert("default features".into(), "disabled".into());
+            }
+            details.extra.insert("profile".into(), install.profile);
+            details.extra.insert("target".into(), install.target);
+            details
+                .extra
+                .insert("binaries".into(), install.bins.join(", "));
+        }
+
+        if let Some(crate_) = crate_ {
+            if !details.installed {
+                details.version = crate_
+                    .crate_
+                    .max_stable_version
+                    .or_else(|| crate_.versions.first().map(|v| v.num.clone()))
+                    .unwrap_or_default();
+            }
+            details.description = crate_.crate_.description;
+            details.homepage = crate_.crate_.homepage.or(crate_.crate_.repository);
+            if let Some(version) = crate_.versions.iter().find(|v| v.num == details.version) {
+                details.licenses = version.license.iter().cloned().collect();
+                details.maintainer = version.published_by.as_ref().map(|u| u.login.clone());
+                details.dependencies = self.dependencies(name, &version.num).await?;
+            }
+            details.available = crate_
+                .versions
+                .into_iter()
+                .filter(|v| !v.yanked)
+                .map(|v| v.num)
+                .collect();
+        }
+
+        Ok(Some(details))
+    }
+
+    fn entry(&self, package: &Self::Package) -> Entry {
+        Entry {
+            name: package.name.clone(),
+            version: Some(package.version.clone()),
+            features: package.features.clone(),
+        }
+    }
+
+    async fn resolve(&self, entry: &Entry) -> Result<Option<Self::Package>, Self::Error> {
+        let package = self
+            .search_install(&entry.name)
+            .await?
+            .into_iter()
+            .find(|p| p.name == entry.name);
+        Ok(package.map(|package| Package {
+            version: entry.version.clone().unwrap_or(package.version),
+            features: entry.features.clone(),
+            ..package
+        }))
+    }
+
+    async fn exports(&self, package: &Self::Package) -> Result<Exports, Self::Error> {
+        let home_dir = dirs::home_dir().ok_or(Error::NoHomeDirectory)?;
+        let bin_dir = home_dir.join(".cargo/bin");
+        Ok(Exports {
+            bins: package.bins.iter().map(|bin| bin_dir.join(bin)).collect(),
+            ..Exports::default()
         })
-        .await
+    }
+
+    /// Crate whose binary is `path`, in `~/.cargo/bin`.
+    async fn owns(&self, path: &Path) -> Result<Option<Self::Package>, Self::Error> {
+        let home_dir = dirs::home_dir().ok_or(Error::NoHomeDirectory)?;
+        let bin_dir = home_dir.join(".cargo/bin");
+        if path.parent() != Some(bin_dir.as_path()) {
+            return Ok(None);
+        }
+        let Some(bin) = path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(None);
+        };
+        let packages = self.list().await?;
+        Ok(packages
+            .into_iter()
+            .find(|package| package.bins.iter().any(|b| b == bin)))
+    }
+
+    /// Downloaded and extracted sources of the cargo registries.
+    async fn cache_usage(&self, keep: usize) -> Result<CacheUsage, Self::Error> {
+        Ok(self.cache_files(keep)?.usage())
+    }
+
+    async fn clean_cache(&self, keep: usize) -> Result<u64, Self::Error> {
+        self.cache_files(keep)?.clean().map_err(Error::Fs)
+    }
+
+    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
+        self.inner_install(package).await
     }
 
     async fn uninstall(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("cargo")
-            .args(["uninstall", &package.name])
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|err| format!("Failed to uninstall package: {}", err))?;
-        Ok(())
+        command::run(Command::new("cargo").args(["uninstall", &package.name]))
+            .map_err(Error::Command)
+    }
+
+    async fn uninstall_many(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        command::run(
+            Command::new("cargo")
+                .arg("uninstall")
+                .args(packages.iter().map(|package| package.name.as_str())),
+        )
+        .map_err(Error::Command)
+    }
+
+    /// Reinstalls the previous version with the features it is built with.
+    async fn revert(&self, change: &Change) -> Result<bool, Self::Error> {
+        let version = match (change.action, &change.old_version) {
+            (Action::Upgrade | Action::Downgrade, Some(version)) => version,
+            _ => return super::revert(self, change).await,
+        };
+        let Some(package) = self.find(&change.package).await? else {
+            return Ok(false);
+        };
+        self.install(&Package {
+            version: version.clone(),
+            ..package
+        })
+        .await?;
+        Ok(true)
     }
 
     async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error> {
@@ -304,9 +673,7 @@ impl Manager for Cargo {
         let handles = all_packages
             .into_iter()
             .filter_map(|package| {
-                let Some(repository) = package.repository.clone() else {
-                    return None;
-                };
+                let repository = package.repository.clone()?;
                 let Ok(current_version) = Version::parse(&package.version) else {
                     return None;
                 };
@@ -376,6 +743,7 @@ impl Manager for Cargo {
             }
         }
 
+        let packages = pins::filter_updates(Self::NAME, packages).map_err(Error::Pins)?;
         self.update_cache.write().unwrap().replace(packages.clone());
 
         Ok(packages)
@@ -386,37 +754,30 @@ impl Manager for Cargo {
     }
 
     async fn update(&self) -> Result<(), Self::Error> {
-        println!("cache: {}", self.update_cache.read().unwrap().is_some());
         if self.update_cache.read().unwrap().is_none() {
             self.list_updates().await?;
         }
         let packages = self.update_cache.write().unwrap().take().unwrap();
-        let length = packages.len();
+        let total = packages.len();
+        report(
+            &self.progress_sender,
+            ProgressEvent::Started { total: Some(total) },
+        )
+        .await;
+        let mut result = Ok(());
         for (i, package) in packages.iter().enumerate() {
-            if let Some(sender) = self.progress_sender.as_ref() {
-                let _ = sender
-                    .send(format!("{}% {}", i * 100 / packages.len(), package.name))
-                    .await;
+            let step = ProgressEvent::Step {
+                step: i + 1,
+                total,
+                package: package.name.clone(),
+            };
+            report(&self.progress_sender, step).await;
+            result = self.inner_install(package).await;
+            if result.is_err() {
+                break;
             }
-
-            self.inner_install(package, |name| async move {
-                let Some(progress_sender) = &self.progress_sender.clone() else {
-                    return;
-                };
-                let _ = progress_sender
-                    .send(format!(
-                        "{}% {} {}",
-                        i * 100 / length,
-                        package.name.clone(),
-                        name
-                    ))
-                    .await;
-            })
-            .await?;
-        }
-        if let Some(sender) = self.progress_sender.as_ref() {
-            let _ = sender.send("100%".into()).await;
         }
-        Ok(())
+        report(&self.progress_sender, ProgressEvent::Finished).await;
+        result
     }
 }
@@ -0,0 +1,59 @@
+use super::PackageInfo;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Everything a manager knows about a package, as shown by `unipac info`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PackageDetails {
+    pub name: String,
+    /// Installed version, or the one that would be installed.
+    pub version: String,
+    pub installed: bool,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub origin: Option<String>,
+    pub maintainer: Option<String>,
+    pub licenses: Vec<String>,
+    pub dependencies: Vec<String>,
+    /// Installed size in bytes, when the manager knows it.
+    pub installed_size: Option<u64>,
+    pub install_date: Option<DateTime<Local>>,
+    /// Versions, or channels, the package is available in.
+    pub available: Vec<String>,
+    /// Manager-specific fields, like the features a crate is built with.
+    pub extra: BTreeMap<String, String>,
+}
+impl PackageDetails {
+    /// Details of `package`, limited to what [`PackageInfo`] exposes.
+    pub fn new<P: PackageInfo>(package: &P, installed: bool) -> Self {
+        Self {
+            name: package.name().into(),
+            version: package.version().into(),
+            installed,
+            description: package.description().map(String::from),
+            homepage: package.homepage().map(String::from),
+            origin: package.origin().map(String::from),
+            installed_size: package.installed_size(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Parses a human-readable size like `243.4 MB`, as printed by flatpak.
+#[cfg(feature = "flatpak")]
+pub(crate) fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let unit_start = size.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = size.split_at(unit_start);
+    let number: f64 = number.trim().parse().ok()?;
+    let multiplier = match unit {
+        "B" | "bytes" => 1e0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
@@ -0,0 +1,530 @@
+use super::{
+    CacheUsage, ErrorKind, Exports, Manager, ManagerError, PackageDetails, PackageInfo, PlanItem,
+};
+use crate::history::Change;
+use crate::manifest::Entry;
+use std::any::Any;
+use std::borrow::Cow;
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+trait AnyPackageInner: PackageInfo + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+}
+impl<P> AnyPackageInner for P
+where
+    P: PackageInfo + Any + Send + Sync,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A package coming from any manager, usable through [`DynManager`].
+///
+/// The concrete package is kept as is and can be recovered with
+/// [`AnyPackage::downcast_ref`].
+pub struct AnyPackage {
+    manager: &'static str,
+    inner: Box<dyn AnyPackageInner>,
+}
+impl AnyPackage {
+    pub fn new<P>(manager: &'static str, package: P) -> Self
+    where
+        P: PackageInfo + Any + Send + Sync,
+    {
+        Self {
+            manager,
+            inner: Box::new(package),
+        }
+    }
+
+    pub fn manager(&self) -> &'static str {
+        self.manager
+    }
+
+    pub fn downcast_ref<P: Any>(&self) -> Option<&P> {
+        self.inner.as_any().downcast_ref()
+    }
+}
+impl PackageInfo for AnyPackage {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    fn version(&self) -> &str {
+        self.inner.version()
+    }
+    fn description(&self) -> Option<&str> {
+        self.inner.description()
+    }
+    fn homepage(&self) -> Option<&str> {
+        self.inner.homepage()
+    }
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        self.inner.origin()
+    }
+    fn installed_size(&self) -> Option<u64> {
+        self.inner.installed_size()
+    }
+}
+
+/// An error coming from any manager, usable through [`DynManager`].
+pub struct AnyError {
+    manager: &'static str,
+    inner: Box<dyn ManagerError>,
+}
+impl AnyError {
+    pub fn new<E: ManagerError>(manager: &'static str, error: E) -> Self {
+        Self {
+            manager,
+            inner: Box::new(error),
+        }
+    }
+
+    pub fn manager(&self) -> &'static str {
+        self.manager
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.inner.kind()
+    }
+}
+impl Display for AnyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.manager, self.inner)
+    }
+}
+impl Debug for AnyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnyError")
+            .field("manager", &self.manager)
+            .field("error", &self.inner)
+            .finish()
+    }
+}
+impl std::error::Error for AnyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.inner)
+    }
+}
+impl ManagerError for AnyError {
+    fn kind(&self) -> ErrorKind {
+        self.inner.kind()
+    }
+}
+
+/// A package given to a manager it does not come from.
+#[derive(Debug)]
+struct ForeignPackage {
+    from: &'static str,
+    to: &'static str,
+}
+impl Display for ForeignPackage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Package from {} given to {}", self.from, self.to)
+    }
+}
+impl std::error::Error for ForeignPackage {}
+impl ManagerError for ForeignPackage {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// Object-safe counterpart of [`Manager`].
+///
+/// Every [`Manager`] implements it, so backends can be stored together as
+/// `Box<dyn DynManager>` and driven at runtime.
+pub trait DynManager: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>>;
+    fn list_explicit(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>>;
+    fn find<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Option<AnyPackage>, AnyError>>;
+    fn search<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Result<Vec<AnyPackage>, AnyError>>;
+    fn search_install<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<AnyPackage>, AnyError>>;
+    fn install<'a>(&'a self, package: &'a AnyPackage) -> BoxFuture<'a, Result<(), AnyError>>;
+    fn uninstall<'a>(&'a self, package: &'a AnyPackage) -> BoxFuture<'a, Result<(), AnyError>>;
+    fn install_many<'a>(
+        &'a self,
+        packages: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>>;
+    fn uninstall_many<'a>(
+        &'a self,
+        packages: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>>;
+    fn list_updates(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>>;
+    fn count_updates(&self) -> BoxFuture<'_, Result<usize, AnyError>>;
+    fn update(&self) -> BoxFuture<'_, Result<(), AnyError>>;
+    fn update_packages<'a>(
+        &'a self,
+        packages: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>>;
+    fn plan_install<'a>(
+        &'a self,
+        package: &'a AnyPackage,
+    ) -> BoxFuture<'a, Result<Vec<PlanItem>, AnyError>>;
+    fn plan_uninstall<'a>(
+        &'a self,
+        package: &'a AnyPackage,
+    ) -> BoxFuture<'a, Result<Vec<PlanItem>, AnyError>>;
+    fn plan_update(&self) -> BoxFuture<'_, Result<Vec<PlanItem>, AnyError>>;
+    fn entry(&self, package: &AnyPackage) -> Result<Entry, AnyError>;
+    fn resolve<'a>(
+        &'a self,
+        entry: &'a Entry,
+    ) -> BoxFuture<'a, Result<Option<AnyPackage>, AnyError>>;
+    fn info<'a>(&'a self, name: &'a str)
+        -> BoxFuture<'a, Result<Option<PackageDetails>, AnyError>>;
+    fn owns<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Option<AnyPackage>, AnyError>>;
+    fn list_orphans(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>>;
+    fn remove_orphans<'a>(
+        &'a self,
+        orphans: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>>;
+    fn exports<'a>(&'a self, package: &'a AnyPackage) -> BoxFuture<'a, Result<Exports, AnyError>>;
+    fn cache_usage(&self, keep: usize) -> BoxFuture<'_, Result<CacheUsage, AnyError>>;
+    fn clean_cache(&self, keep: usize) -> BoxFuture<'_, Result<u64, AnyError>>;
+    fn set_held<'a>(
+        &'a self,
+        package: &'a AnyPackage,
+        held: bool,
+    ) -> BoxFuture<'a, Result<(), AnyError>>;
+    fn revert<'a>(&'a self, change: &'a Change) -> BoxFuture<'a, Result<bool, AnyError>>;
+}
+
+fn wrap<M>(packages: Vec<M::Package>) -> Vec<AnyPackage>
+where
+    M: Manager,
+    M::Package: 'static,
+{
+    packages
+        .into_iter()
+        .map(|package| AnyPackage::new(M::NAME, package))
+        .collect()
+}
+
+fn unwrap<M>(package: &AnyPackage) -> Result<&M::Package, AnyError>
+where
+    M: Manager,
+    M::Package: 'static,
+{
+    package.downcast_ref().ok_or_else(|| {
+        AnyError::new(
+            M::NAME,
+            ForeignPackage {
+                from: package.manager(),
+                to: M::NAME,
+            },
+        )
+    })
+}
+
+impl<M> DynManager for M
+where
+    M: Manager + Send,
+    M::Package: 'static,
+{
+    fn name(&self) -> &'static str {
+        M::NAME
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::list(self)
+                .await
+                .map(wrap::<M>)
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn list_explicit(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::list_explicit(self)
+                .await
+                .map(wrap::<M>)
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn find<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Option<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::find(self, name)
+                .await
+                .map(|package| package.map(|package| AnyPackage::new(M::NAME, package)))
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn search<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Result<Vec<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::search(self, query)
+                .await
+                .map(wrap::<M>)
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn search_install<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::search_install(self, query)
+                .await
+                .map(wrap::<M>)
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn install<'a>(&'a self, package: &'a AnyPackage) -> BoxFuture<'a, Result<(), AnyError>> {
+        Box::pin(async move {
+            let package = unwrap::<M>(package)?;
+            Manager::install(self, package)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn uninstall<'a>(&'a self, package: &'a AnyPackage) -> BoxFuture<'a, Result<(), AnyError>> {
+        Box::pin(async move {
+            let package = unwrap::<M>(package)?;
+            Manager::uninstall(self, package)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn install_many<'a>(
+        &'a self,
+        packages: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>> {
+        Box::pin(async move {
+            let packages = packages
+                .iter()
+                .map(unwrap::<M>)
+                .collect::<Result<Vec<_>, _>>()?;
+            Manager::install_many(self, &packages)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn uninstall_many<'a>(
+        &'a self,
+        packages: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>> {
+        Box::pin(async move {
+            let packages = packages
+                .iter()
+                .map(unwrap::<M>)
+                .collect::<Result<Vec<_>, _>>()?;
+            Manager::uninstall_many(self, &packages)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn list_updates(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::list_updates(self)
+                .await
+                .map(wrap::<M>)
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn count_updates(&self) -> BoxFuture<'_, Result<usize, AnyError>> {
+        Box::pin(async move {
+            Manager::count_updates(self)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn update(&self) -> BoxFuture<'_, Result<(), AnyError>> {
+        Box::pin(async move {
+            Manager::update(self)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn update_packages<'a>(
+        &'a self,
+        packages: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>> {
+        Box::pin(async move {
+            let packages = packages
+                .iter()
+                .map(unwrap::<M>)
+                .collect::<Result<Vec<_>, _>>()?;
+            Manager::update_packages(self, &packages)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn plan_install<'a>(
+        &'a self,
+        package: &'a AnyPackage,
+    ) -> BoxFuture<'a, Result<Vec<PlanItem>, AnyError>> {
+        Box::pin(async move {
+            let package = unwrap::<M>(package)?;
+            Manager::plan_install(self, package)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn plan_uninstall<'a>(
+        &'a self,
+        package: &'a AnyPackage,
+    ) -> BoxFuture<'a, Result<Vec<PlanItem>, AnyError>> {
+        Box::pin(async move {
+            let package = unwrap::<M>(package)?;
+            Manager::plan_uninstall(self, package)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn plan_update(&self) -> BoxFuture<'_, Result<Vec<PlanItem>, AnyError>> {
+        Box::pin(async move {
+            Manager::plan_update(self)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn entry(&self, package: &AnyPackage) -> Result<Entry, AnyError> {
+        unwrap::<M>(package).map(|package| Manager::entry(self, package))
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        entry: &'a Entry,
+    ) -> BoxFuture<'a, Result<Option<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::resolve(self, entry)
+                .await
+                .map(|package| package.map(|package| AnyPackage::new(M::NAME, package)))
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn info<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<PackageDetails>, AnyError>> {
+        Box::pin(async move {
+            Manager::info(self, name)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn owns<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Option<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::owns(self, path)
+                .await
+                .map(|package| package.map(|package| AnyPackage::new(M::NAME, package)))
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn list_orphans(&self) -> BoxFuture<'_, Result<Vec<AnyPackage>, AnyError>> {
+        Box::pin(async move {
+            Manager::list_orphans(self)
+                .await
+                .map(wrap::<M>)
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn remove_orphans<'a>(
+        &'a self,
+        orphans: &'a [AnyPackage],
+    ) -> BoxFuture<'a, Result<(), AnyError>> {
+        Box::pin(async move {
+            let orphans = orphans
+                .iter()
+                .map(unwrap::<M>)
+                .collect::<Result<Vec<_>, _>>()?;
+            Manager::remove_orphans(self, &orphans)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn exports<'a>(&'a self, package: &'a AnyPackage) -> BoxFuture<'a, Result<Exports, AnyError>> {
+        Box::pin(async move {
+            let package = unwrap::<M>(package)?;
+            Manager::exports(self, package)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn cache_usage(&self, keep: usize) -> BoxFuture<'_, Result<CacheUsage, AnyError>> {
+        Box::pin(async move {
+            Manager::cache_usage(self, keep)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn clean_cache(&self, keep: usize) -> BoxFuture<'_, Result<u64, AnyError>> {
+        Box::pin(async move {
+            Manager::clean_cache(self, keep)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn set_held<'a>(
+        &'a self,
+        package: &'a AnyPackage,
+        held: bool,
+    ) -> BoxFuture<'a, Result<(), AnyError>> {
+        Box::pin(async move {
+            let package = unwrap::<M>(package)?;
+            Manager::set_held(self, package, held)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+
+    fn revert<'a>(&'a self, change: &'a Change) -> BoxFuture<'a, Result<bool, AnyError>> {
+        Box::pin(async move {
+            Manager::revert(self, change)
+                .await
+                .map_err(|err| AnyError::new(M::NAME, err))
+        })
+    }
+}
+
+/// Every manager enabled at compile time, in the order of `unipac_core::MANAGERS`.
+pub fn registry() -> Vec<Box<dyn DynManager>> {
+    vec![
+        #[cfg(feature = "pacman")]
+        Box::new(super::Pacman::new()),
+        #[cfg(feature = "aur")]
+        Box::new(super::AUR::new()),
+        #[cfg(feature = "flatpak")]
+        Box::new(super::Flatpak::new()),
+        #[cfg(feature = "snap")]
+        Box::new(super::Snap::new()),
+        #[cfg(feature = "git")]
+        Box::new(super::Git::new()),
+        #[cfg(feature = "cargo")]
+        Box::new(super::Cargo::new()),
+    ]
+}
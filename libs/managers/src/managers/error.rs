@@ -0,0 +1,47 @@
+use std::fmt::Display;
+
+/// Broad category of a manager failure, used to pick an exit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// A remote could not be reached or answered with an error.
+    Network,
+    /// Some output, file or response could not be understood.
+    Parse,
+    /// An external command failed.
+    Command,
+    PermissionDenied,
+    NotFound,
+    /// The package database is locked by another process.
+    Locked,
+    /// Packages conflict, or their dependencies cannot be satisfied.
+    Conflict,
+    Other,
+}
+impl ErrorKind {
+    pub fn of_io(error: &std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            std::io::ErrorKind::NotFound => Self::NotFound,
+            _ => Self::Other,
+        }
+    }
+}
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network => write!(f, "network"),
+            Self::Parse => write!(f, "parse"),
+            Self::Command => write!(f, "command"),
+            Self::PermissionDenied => write!(f, "permission denied"),
+            Self::NotFound => write!(f, "not found"),
+            Self::Locked => write!(f, "locked"),
+            Self::Conflict => write!(f, "conflict"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Error returned by a [`Manager`](super::Manager).
+pub trait ManagerError: std::error::Error + Send + Sync + 'static {
+    fn kind(&self) -> ErrorKind;
+}
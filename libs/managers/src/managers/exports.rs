@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+/// What a package exposes to the rest of the system, telling the same
+/// software apart when several managers installed it.
+#[derive(Clone, Debug, Default)]
+pub struct Exports {
+    /// Executables put on `$PATH`.
+    pub bins: Vec<PathBuf>,
+    /// Names of the desktop entries, without the `.desktop` extension.
+    pub desktop_entries: Vec<String>,
+}
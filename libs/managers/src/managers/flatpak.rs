@@ -1,158 +1,793 @@
+use super::details::parse_size;
+use super::{
+    report, Action, CacheUsage, ErrorKind, Exports, ManagerError, PackageDetails, PackageInfo,
+    ProgressEvent,
+};
+use crate::history::Change;
+use crate::manifest::Entry;
+use crate::pins;
+use crate::utils::cache::{self, CacheFiles};
+use crate::utils::command::{self, CommandError};
+use crate::utils::config;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::io::{BufRead as _, BufReader};
-use std::process::{Command, Stdio};
-use std::str::FromStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::Mutex;
 use tokio::sync::mpsc::Sender;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum Error {
-    Format,
-    Command,
+    Parse { line: String },
+    Command(CommandError),
+    Pins(pins::Error),
+    Fs(std::io::Error),
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Format => write!(f, "Format error"),
-            Self::Command => write!(f, "Command error"),
+            Self::Parse { line } => write!(f, "Unexpected output: {}", line),
+            Self::Command(err) => write!(f, "Command error: {}", err),
+            Self::Pins(err) => write!(f, "Pins: {}", err),
+            Self::Fs(err) => write!(f, "File system: {}", err),
         }
     }
 }
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse { .. } => None,
+            Self::Command(err) => Some(err),
+            Self::Pins(err) => Some(err),
+            Self::Fs(err) => Some(err),
+        }
+    }
+}
+impl ManagerError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Parse { .. } => ErrorKind::Parse,
+            Self::Command(err) => err.kind(),
+            Self::Pins(err) => err.kind(),
+            Self::Fs(err) => ErrorKind::of_io(err),
+        }
+    }
+}
+
+/// Temporary directories flatpak leaves in `/var/tmp` when interrupted.
+fn cache_files() -> CacheFiles {
+    let entries = cache::entries(Path::new("/var/tmp"))
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("flatpak-cache-"))
+        })
+        .collect::<Vec<_>>();
+    CacheFiles {
+        stale: entries.clone(),
+        entries,
+    }
+}
 
-fn get_next<'a>(parts: &mut impl Iterator<Item = &'a str>) -> Result<String, Error> {
-    parts.next().map(String::from).ok_or(Error::Format)
+/// Columns requested from `flatpak list`.
+const LIST_COLUMNS: &str = "application,name,version,branch,description,installation,origin,size";
+/// Columns requested from `flatpak search`, which knows the remotes but not the installations.
+const SEARCH_COLUMNS: &str = "application,name,version,branch,description,remotes";
+/// Columns requested from `flatpak remote-ls --updates`.
+const UPDATE_COLUMNS: &str = "application,name,version,branch,description,origin";
+
+/// Pairs the values of a line printed with `--columns=` `names` with their column.
+fn columns<'a>(line: &'a str, names: &'a str) -> Result<Vec<(&'a str, &'a str)>, Error> {
+    let values = line.split('\t').map(str::trim).collect::<Vec<_>>();
+    let names = names.split(',').collect::<Vec<_>>();
+    if values.len() != names.len() {
+        return Err(Error::Parse { line: line.into() });
+    }
+    Ok(names.into_iter().zip(values).collect())
+}
+
+/// Lines of `output` holding columns, flatpak printing a message instead
+/// when there is nothing to list.
+fn rows(output: &str) -> impl Iterator<Item = &str> {
+    output.lines().filter(|line| line.contains('\t'))
+}
+
+/// Splits `packages` by `key`, for a command to take each group.
+fn group_by<'a, K: Ord>(
+    packages: &[&'a Package],
+    key: impl Fn(&Package) -> K,
+) -> BTreeMap<K, Vec<&'a Package>> {
+    let mut groups = BTreeMap::<K, Vec<&Package>>::new();
+    for package in packages {
+        groups.entry(key(package)).or_default().push(*package);
+    }
+    groups
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Package {
     pub id: String,
     pub name: String,
     pub version: String,
     pub branch: String,
     pub description: String,
+    /// Installation the package is deployed in, or is to be installed to.
+    pub installation: Option<Installation>,
+    /// Remote the package comes from, or is to be installed from.
+    pub origin: Option<String>,
+    pub installed_size: Option<u64>,
 }
-impl FromStr for Package {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = s.split("\t");
-        let name = get_next(&mut parts)?;
-        let id = get_next(&mut parts)?;
-        let version = get_next(&mut parts)?;
-        let branch = get_next(&mut parts)?;
-        let description = get_next(&mut parts)?;
-        Ok(Package {
-            id,
-            name,
-            version,
-            branch,
-            description,
+impl Package {
+    /// Parses a line printed with `--columns=` `names`.
+    fn parse(line: &str, names: &str) -> Result<Self, Error> {
+        let mut package = Self::default();
+        for (name, value) in columns(line, names)? {
+            let value = value.to_string();
+            match name {
+                "application" => package.id = value,
+                "name" => package.name = value,
+                "version" => package.version = value,
+                "branch" => package.branch = value,
+                "description" => package.description = value,
+                "installation" => package.installation = Some(value.into()),
+                "origin" => package.origin = Some(value).filter(|origin| !origin.is_empty()),
+                // Remotes offering the package, comma-separated
+                "remotes" => {
+                    package.origin = value
+                        .split(',')
+                        .next()
+                        .filter(|origin| !origin.is_empty())
+                        .map(String::from)
+                }
+                "size" => package.installed_size = parse_size(&value),
+                _ => {}
+            }
+        }
+        Ok(package)
+    }
+
+    /// `--user`, `--system` or `--installation=` flag of the installation
+    /// the package is in, if known.
+    fn installation_flag(&self) -> Option<String> {
+        self.installation.as_ref().map(Installation::flag)
+    }
+}
+
+impl PackageInfo for Package {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn version(&self) -> &str {
+        &self.version
+    }
+    fn identifier(&self) -> Option<Cow<'_, str>> {
+        Some(self.id.as_str().into())
+    }
+    fn description(&self) -> Option<&str> {
+        Some(&self.description)
+    }
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        let origin = self.origin.as_deref()?;
+        Some(match &self.installation {
+            Some(installation) => format!("{}, {}", origin, installation).into(),
+            None => origin.into(),
         })
     }
+    fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+}
+
+/// Parses the output of `flatpak info` and `flatpak remote-info` into `details`.
+fn parse_info(output: &str, details: &mut PackageDetails) {
+    let mut lines = output.lines().skip_while(|line| line.trim().is_empty());
+    // `Firefox - Fast, Private & Safe Web Browser`
+    if let Some((name, description)) = lines.next().and_then(|line| line.split_once(" - ")) {
+        details.name = name.trim().into();
+        details.description = Some(description.trim().into());
+    }
+    for line in lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Version" => details.version = value.into(),
+            "License" => details.licenses = vec![value.into()],
+            "Origin" => details.origin = Some(value.into()),
+            "Installed" => details.installed_size = parse_size(value),
+            "Runtime" => details.dependencies.push(value.into()),
+            "ID" | "Branch" | "Installation" => {
+                details
+                    .extra
+                    .insert(key.trim().to_lowercase(), value.into());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Where flatpak deploys packages.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(from = "String")]
+pub enum Installation {
+    /// `~/.local/share/flatpak`.
+    #[default]
+    User,
+    /// `/var/lib/flatpak`.
+    System,
+    /// Installation declared in `/etc/flatpak/installations.d`, by name.
+    Custom(String),
+}
+impl Installation {
+    pub fn flag(&self) -> String {
+        match self {
+            Self::User => "--user".into(),
+            Self::System => "--system".into(),
+            Self::Custom(name) => format!("--installation={}", name),
+        }
+    }
+}
+impl From<String> for Installation {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "user" => Self::User,
+            "system" | "default" => Self::System,
+            _ => Self::Custom(value),
+        }
+    }
+}
+impl Display for Installation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::System => write!(f, "system"),
+            Self::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+impl Serialize for Installation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Options of `flatpak remotes` other than the installation of the remote.
+const REMOTE_FLAGS: [&str; 4] = [
+    "disabled",
+    "no-gpg-verify",
+    "no-enumerate",
+    "no-use-for-deps",
+];
+
+/// A repository flatpak installs packages from.
+#[derive(Clone, Debug, Serialize)]
+pub struct Remote {
+    pub name: String,
+    pub title: Option<String>,
+    pub url: String,
+    pub installation: Installation,
+    pub disabled: bool,
+}
+impl Remote {
+    /// Parses a line of `flatpak remotes --columns=name,title,url,options`,
+    /// the options holding the installation among flags like `disabled`.
+    fn parse(line: &str) -> Result<Self, Error> {
+        let values = line.split('\t').map(str::trim).collect::<Vec<_>>();
+        let [name, title, url, options] = values[..] else {
+            return Err(Error::Parse { line: line.into() });
+        };
+        let options = options.split(',').map(str::trim).collect::<Vec<_>>();
+        let installation = options
+            .iter()
+            .find(|option| {
+                !option.is_empty()
+                    && !REMOTE_FLAGS.contains(option)
+                    && !option.starts_with("filtered")
+            })
+            .map_or(Installation::System, |option| option.to_string().into());
+        Ok(Self {
+            name: name.into(),
+            title: Some(title.to_string()).filter(|title| !title.is_empty()),
+            url: url.into(),
+            installation,
+            disabled: options.contains(&"disabled"),
+        })
+    }
+}
+
+/// `[flatpak]` table of the configuration file.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Installation new packages go to.
+    pub installation: Installation,
 }
 
 pub struct Flatpak {
-    progress_sender: Option<Sender<String>>,
+    progress_sender: Option<Sender<ProgressEvent>>,
     update_cache: Mutex<Option<Vec<Package>>>,
+    options: Options,
 }
 impl Flatpak {
     pub fn new() -> Self {
         Self {
             progress_sender: None,
             update_cache: None.into(),
+            options: config::section("flatpak"),
         }
     }
-    pub fn with_progress(progress_sender: Sender<String>) -> Self {
+    pub fn with_progress(progress_sender: Sender<ProgressEvent>) -> Self {
         Self {
             progress_sender: progress_sender.into(),
             update_cache: None.into(),
+            options: config::section("flatpak"),
+        }
+    }
+
+    /// Runs `flatpak update`, reporting a step for each of the `updates` it gets to.
+    async fn stream_update(&self, updates: &[Package]) -> Result<(), Error> {
+        let mut stream =
+            command::stream_stdout(Command::new("flatpak").args(["update", "--noninteractive"]))
+                .map_err(Error::Command)?;
+        let mut step = 0;
+        for line in stream.by_ref() {
+            let line = line.trim();
+            let package = line
+                .strip_prefix("Updating ")
+                .and_then(|rest| rest.split(' ').next())
+                .filter(|name| updates.iter().any(|p| name.contains(&p.name)));
+            let event = match package {
+                Some(package) => {
+                    step += 1;
+                    ProgressEvent::Step {
+                        step,
+                        total: updates.len(),
+                        package: package.into(),
+                    }
+                }
+                None if line.is_empty() => continue,
+                None => ProgressEvent::Log(line.into()),
+            };
+            report(&self.progress_sender, event).await;
         }
+        stream.wait().map_err(Error::Command)
+    }
+
+    /// Remotes of every installation, disabled ones included.
+    pub fn remotes(&self) -> Result<Vec<Remote>, Error> {
+        let output = command::output(Command::new("flatpak").args([
+            "remotes",
+            "--show-disabled",
+            "--columns=name,title,url,options",
+        ]))
+        .map_err(Error::Command)?;
+        rows(&output).map(Remote::parse).collect()
+    }
+
+    /// Adds the remote `name`, `url` being its repository or a `.flatpakrepo`
+    /// file, to `installation` or the configured one.
+    pub fn add_remote(
+        &self,
+        name: &str,
+        url: &str,
+        installation: Option<&Installation>,
+    ) -> Result<(), Error> {
+        let installation = installation.unwrap_or(&self.options.installation);
+        command::run(Command::new("flatpak").args([
+            "remote-add",
+            "--if-not-exists",
+            &installation.flag(),
+            name,
+            url,
+        ]))
+        .map_err(Error::Command)
+    }
+
+    /// Removes the remote `name` of `installation`, or of the configured one.
+    pub fn remove_remote(
+        &self,
+        name: &str,
+        installation: Option<&Installation>,
+    ) -> Result<(), Error> {
+        let installation = installation.unwrap_or(&self.options.installation);
+        command::run(Command::new("flatpak").args(["remote-delete", &installation.flag(), name]))
+            .map_err(Error::Command)
+    }
+
+    /// Installation `package` goes to, the configured one unless it has its own.
+    fn target(&self, package: &Package) -> String {
+        package
+            .installation
+            .as_ref()
+            .unwrap_or(&self.options.installation)
+            .flag()
+    }
+
+    /// `flatpak info --show-<field>` of an installed package.
+    fn show(&self, package: &Package, field: &str) -> Result<String, Error> {
+        command::output(
+            Command::new("flatpak")
+                .arg("info")
+                .args(package.installation_flag())
+                .args([&format!("--show-{}", field), &package.id]),
+        )
+        .map(|output| output.trim().into())
+        .map_err(Error::Command)
+    }
+
+    /// Commit of the remote history where `package` is at `version`.
+    fn find_commit(&self, package: &Package, version: &str) -> Result<Option<String>, Error> {
+        let origin = match &package.origin {
+            Some(origin) => origin.clone(),
+            None => self.show(package, "origin")?,
+        };
+        let ref_ = self.show(package, "ref")?;
+        let remote_info = |args: &[&str]| {
+            command::output(
+                Command::new("flatpak")
+                    .arg("remote-info")
+                    .args(package.installation_flag())
+                    .args(args)
+                    .args([&origin, &ref_]),
+            )
+            .map_err(Error::Command)
+        };
+        let log = remote_info(&["--log"])?;
+        let commits = log
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("Commit:"))
+            .map(str::trim);
+        for commit in commits {
+            let info = remote_info(&[format!("--commit={}", commit).as_str()])?;
+            if info
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("Version:"))
+                .any(|v| v.trim() == version)
+            {
+                return Ok(Some(commit.into()));
+            }
+        }
+        Ok(None)
+    }
+}
+impl Default for Flatpak {
+    fn default() -> Self {
+        Self::new()
     }
 }
 impl super::Manager for Flatpak {
+    const NAME: &'static str = "flatpak";
+
     type Package = Package;
     type Error = Error;
 
     async fn list(&self) -> Result<Vec<Self::Package>, Self::Error> {
-        Command::new("flatpak")
-            .arg("list")
-            .output()
-            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
-            .map_err(|_| Error::Command)?
-            .lines()
-            .filter(|s| s.contains("\t"))
-            .map(Package::from_str)
+        let output = command::output(
+            Command::new("flatpak").args(["list", &format!("--columns={}", LIST_COLUMNS)]),
+        )
+        .map_err(Error::Command)?;
+        rows(&output)
+            .map(|line| Package::parse(line, LIST_COLUMNS))
             .collect()
     }
 
     async fn find(&self, name: &str) -> Result<Option<Self::Package>, Self::Error> {
-        let name = name.to_lowercase();
-        let packages = self.list().await?;
-        let package = packages
-            .into_iter()
-            .find(|p| p.name.to_lowercase() == name || p.id.contains(&name));
-        Ok(package)
+        let mut packages = self.list().await?;
+        let index = packages
+            .iter()
+            .position(|p| p.id.eq_ignore_ascii_case(name))
+            .or_else(|| {
+                packages
+                    .iter()
+                    .position(|p| p.name.eq_ignore_ascii_case(name))
+            });
+        Ok(index.map(|index| packages.swap_remove(index)))
     }
 
     async fn search(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error> {
-        Command::new("flatpak")
-            .args(["search", query])
-            .output()
-            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
-            .map_err(|_| Error::Command)?
-            .lines()
-            .filter_map(|s| s.contains("\t").then(|| Package::from_str(s)))
+        let output = command::output(Command::new("flatpak").args([
+            "search",
+            &format!("--columns={}", SEARCH_COLUMNS),
+            query,
+        ]))
+        .map_err(Error::Command)?;
+        rows(&output)
+            .map(|line| Package::parse(line, SEARCH_COLUMNS))
             .collect()
     }
 
     async fn search_install(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error> {
         let packages = self.search(query).await?;
-        let packages = packages
+        let (by_id, others): (Vec<_>, Vec<_>) = packages
+            .into_iter()
+            .partition(|p| p.id.eq_ignore_ascii_case(query));
+        if !by_id.is_empty() {
+            return Ok(by_id);
+        }
+        let query = query.to_lowercase();
+        let packages = others
             .into_iter()
-            .filter_map(|p| p.name.to_lowercase().contains(query).then(|| p))
+            .filter(|p| p.name.to_lowercase().contains(&query))
             .collect();
         Ok(packages)
     }
 
-    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("flatpak")
-            .args(["install", "--noninteractive", "--user", package.id.as_str()])
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Command)?;
+    /// Details from `flatpak info` when installed, `flatpak remote-info`
+    /// otherwise, with the branches the remote offers.
+    async fn info(&self, name: &str) -> Result<Option<PackageDetails>, Self::Error> {
+        let mut details = PackageDetails::default();
+        let (id, installation) = if let Some(package) = self.find(name).await? {
+            let output = command::output(
+                Command::new("flatpak")
+                    .arg("info")
+                    .args(package.installation_flag())
+                    .arg(&package.id),
+            )
+            .map_err(Error::Command)?;
+            details.installed = true;
+            parse_info(&output, &mut details);
+            (package.id, package.installation)
+        } else {
+            let name = name.to_lowercase();
+            let Some(package) = self
+                .search(&name)
+                .await?
+                .into_iter()
+                .find(|p| p.name.to_lowercase() == name || p.id.to_lowercase() == name)
+            else {
+                return Ok(None);
+            };
+            // The remote search found the package in first, then the others
+            let mut remotes = self.remotes()?;
+            remotes.retain(|remote| !remote.disabled);
+            remotes.sort_by_key(|remote| package.origin.as_ref() != Some(&remote.name));
+            let mut installation = None;
+            for remote in remotes {
+                let output = command::output(Command::new("flatpak").args([
+                    "remote-info",
+                    &remote.installation.flag(),
+                    &remote.name,
+                    &package.id,
+                ]));
+                if let Ok(output) = output {
+                    details.origin = Some(remote.name);
+                    installation = Some(remote.installation);
+                    parse_info(&output, &mut details);
+                    break;
+                }
+            }
+            (package.id, installation)
+        };
+        if let Some(origin) = &details.origin {
+            details.available = command::output(
+                Command::new("flatpak")
+                    .arg("remote-ls")
+                    .args(installation.as_ref().map(Installation::flag))
+                    .args(["--columns=application,branch,version", origin]),
+            )
+            .map_err(Error::Command)?
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split('\t');
+                (parts.next()? == id).then(|| parts.collect::<Vec<_>>().join(" "))
+            })
+            .collect();
+        }
+        if let Some(installation) = installation {
+            details
+                .extra
+                .insert("installation".into(), installation.to_string());
+        }
+        if details.name.is_empty() {
+            details.name = id;
+        }
+        Ok(Some(details))
+    }
+
+    /// Runtimes no installed application uses anymore, as `flatpak uninstall
+    /// --unused` finds them.
+    async fn list_orphans(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        // The closed stdin declines the removal after the table is printed:
+        // ` 1.   org.freedesktop.Platform.GL.default   x86_64   21.08   r`
+        let is_index = |part: &str| {
+            part.strip_suffix('.')
+                .is_some_and(|n| n.parse::<u32>().is_ok())
+        };
+        let unused =
+            command::output_unchecked(Command::new("flatpak").args(["uninstall", "--unused"]))
+                .map_err(Error::Command)?
+                .lines()
+                .filter_map(|line| {
+                    let parts = line.split_whitespace().collect::<Vec<_>>();
+                    match parts.as_slice() {
+                        [index, id, .., branch, _] if is_index(index) => {
+                            Some((id.to_string(), branch.to_string()))
+                        }
+                        _ => None,
+                    }
+                })
+                .collect::<Vec<_>>();
+        if unused.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let output = command::output(Command::new("flatpak").args([
+            "list",
+            "--runtime",
+            &format!("--columns={}", LIST_COLUMNS),
+        ]))
+        .map_err(Error::Command)?;
+        rows(&output)
+            .map(|line| Package::parse(line, LIST_COLUMNS))
+            .filter(|package| {
+                package.as_ref().map_or(true, |package| {
+                    unused.contains(&(package.id.clone(), package.branch.clone()))
+                })
+            })
+            .collect()
+    }
+
+    async fn remove_orphans(&self, orphans: &[&Self::Package]) -> Result<(), Self::Error> {
+        for (installation, orphans) in group_by(orphans, Package::installation_flag) {
+            command::run(
+                Command::new("flatpak")
+                    .args(["uninstall", "--noninteractive"])
+                    .args(installation)
+                    .args(
+                        orphans
+                            .iter()
+                            .map(|package| format!("{}//{}", package.id, package.branch)),
+                    ),
+            )
+            .map_err(Error::Command)?;
+        }
         Ok(())
     }
 
+    /// Launcher of the application in `exports/bin`, and its desktop entry,
+    /// both named after its ID.
+    async fn exports(&self, package: &Self::Package) -> Result<Exports, Self::Error> {
+        let home = std::env::var("HOME").unwrap_or_default();
+        let installations = [
+            PathBuf::from("/var/lib/flatpak"),
+            Path::new(&home).join(".local/share/flatpak"),
+        ];
+        let bins = installations
+            .iter()
+            .map(|installation| installation.join("exports/bin").join(&package.id))
+            .filter(|bin| bin.exists())
+            .collect();
+        Ok(Exports {
+            bins,
+            desktop_entries: vec![package.id.clone()],
+        })
+    }
+
+    /// Application exported as `path` in an `exports/bin` directory, or whose
+    /// deployment `path` is part of.
+    async fn owns(&self, path: &Path) -> Result<Option<Self::Package>, Self::Error> {
+        let components = path.iter().filter_map(|c| c.to_str()).collect::<Vec<_>>();
+        let id = components.windows(4).find_map(|window| match window {
+            ["flatpak", "exports", "bin", id] | ["flatpak", "app", id, _] => Some(*id),
+            _ => None,
+        });
+        let Some(id) = id else {
+            return Ok(None);
+        };
+        let packages = self.list().await?;
+        Ok(packages.into_iter().find(|package| package.id == id))
+    }
+
+    async fn cache_usage(&self, _keep: usize) -> Result<CacheUsage, Self::Error> {
+        Ok(cache_files().usage())
+    }
+
+    async fn clean_cache(&self, _keep: usize) -> Result<u64, Self::Error> {
+        cache_files().clean().map_err(Error::Fs)
+    }
+
+    fn entry(&self, package: &Self::Package) -> Entry {
+        Entry::new(&package.id).with_version(&package.version)
+    }
+
+    async fn resolve(&self, entry: &Entry) -> Result<Option<Self::Package>, Self::Error> {
+        let packages = self.search(&entry.name).await?;
+        Ok(packages.into_iter().find(|p| p.id == entry.name))
+    }
+
+    /// Installs `package` to its installation, or the configured one, from
+    /// its origin remote when it has one.
+    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
+        self.install_many(&[package]).await
+    }
+
     async fn uninstall(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("flatpak")
-            .args(["uninstall", package.id.as_str()])
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Command)?;
+        self.uninstall_many(&[package]).await
+    }
+
+    /// Installs `packages` with a command for each installation and remote.
+    async fn install_many(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        let groups = group_by(packages, |package| {
+            (self.target(package), package.origin.clone())
+        });
+        for ((installation, origin), packages) in groups {
+            command::run(
+                Command::new("flatpak")
+                    .args(["install", "--noninteractive", &installation])
+                    .args(origin)
+                    .args(packages.iter().map(|package| package.id.as_str())),
+            )
+            .map_err(Error::Command)?;
+        }
         Ok(())
     }
 
-    async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error> {
-        let packages: Result<Vec<_>, _> = Command::new("flatpak")
-            .args(["remote-ls", "--updates"])
-            .output()
-            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
-            .map_err(|_| Error::Command)?
-            .lines()
-            .filter_map(|s| s.contains("\t").then(|| Package::from_str(s)))
-            .collect();
+    /// Uninstalls `packages` with a command for each installation, for flatpak
+    /// not to ask which one is meant.
+    async fn uninstall_many(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        for (installation, packages) in group_by(packages, Package::installation_flag) {
+            command::run(
+                Command::new("flatpak")
+                    .args(["uninstall", "--noninteractive"])
+                    .args(installation)
+                    .args(packages.iter().map(|package| package.id.as_str())),
+            )
+            .map_err(Error::Command)?;
+        }
+        Ok(())
+    }
 
-        if let Ok(packages) = packages.clone() {
-            self.update_cache.lock().unwrap().replace(packages.clone());
-        } else {
-            self.update_cache.lock().unwrap().take();
+    /// Updates back to the remote commit of the previous version.
+    async fn revert(&self, change: &Change) -> Result<bool, Self::Error> {
+        let version = match (change.action, &change.old_version) {
+            (Action::Upgrade | Action::Downgrade, Some(version)) => version,
+            _ => return super::revert(self, change).await,
+        };
+        let Some(package) = self.find(&change.package).await? else {
+            return Ok(false);
+        };
+        let Some(commit) = self.find_commit(&package, version)? else {
+            return Ok(false);
+        };
+        command::run(
+            Command::new("flatpak")
+                .args(["update", "--noninteractive"])
+                .args(package.installation_flag())
+                .args([&format!("--commit={}", commit), &package.id]),
+        )
+        .map_err(Error::Command)?;
+        Ok(true)
+    }
+
+    async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        self.update_cache.lock().unwrap().take();
+        let output = command::output(Command::new("flatpak").args([
+            "remote-ls",
+            "--updates",
+            &format!("--columns={}", UPDATE_COLUMNS),
+        ]))
+        .map_err(Error::Command)?;
+        let mut packages = rows(&output)
+            .map(|line| Package::parse(line, UPDATE_COLUMNS))
+            .collect::<Result<Vec<_>, _>>()?;
+        // `remote-ls` has no installation column, the installed deployment has
+        let installed = self.list().await?;
+        for package in &mut packages {
+            package.installation = installed
+                .iter()
+                .find(|p| {
+                    p.id == package.id && p.branch == package.branch && p.origin == package.origin
+                })
+                .and_then(|p| p.installation.clone());
         }
+        let packages = pins::filter_updates(Self::NAME, packages).map_err(Error::Pins)?;
 
-        packages
+        self.update_cache.lock().unwrap().replace(packages.clone());
+        Ok(packages)
     }
 
     async fn count_updates(&self) -> Result<usize, Self::Error> {
@@ -167,35 +802,174 @@ impl super::Manager for Flatpak {
         } else {
             self.list_updates().await?
         };
-        let stdout = Command::new("flatpak")
-            .args(["update", "--noninteractive"])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|_| Error::Command)?
-            .stdout
-            .ok_or(Error::Command)?;
-        let mut i = 0;
-        for line in BufReader::new(stdout).lines() {
-            let Ok(line) = line else {
-                continue;
-            };
-            // println!("{line}");
-            if !line.starts_with("Updating ") || !list.iter().any(|p| line.contains(&p.name)) {
-                continue;
-            }
-            if let Some(package_name) = line.split(" ").nth(1) {
-                if let Some(progress_sender) = &self.progress_sender {
-                    let _ = progress_sender
-                        .send(format!("{}% {}", i * 100 / list.len(), package_name))
-                        .await;
-                }
-            }
-            i += 1;
+        let total = list.len();
+        report(
+            &self.progress_sender,
+            ProgressEvent::Started { total: Some(total) },
+        )
+        .await;
+        let result = self.stream_update(&list).await;
+        report(&self.progress_sender, ProgressEvent::Finished).await;
+        result
+    }
+
+    async fn set_held(&self, package: &Self::Package, held: bool) -> Result<(), Self::Error> {
+        let mut mask = Command::new("flatpak");
+        mask.args(["mask", &self.target(package)]);
+        if !held {
+            mask.arg("--remove");
         }
-        if let Some(progress_sender) = &self.progress_sender {
-            let _ = progress_sender.send("100%".into()).await;
+        command::run(mask.arg(&package.id)).map_err(Error::Command)
+    }
+
+    /// Updates `packages` with a command for each installation, for flatpak
+    /// not to ask which one is meant.
+    async fn update_packages(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        for (installation, packages) in group_by(packages, Package::installation_flag) {
+            command::run(
+                Command::new("flatpak")
+                    .args(["update", "--noninteractive"])
+                    .args(installation)
+                    .args(packages.iter().map(|package| package.id.as_str())),
+            )
+            .map_err(Error::Command)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_pair_values_with_their_names() {
+        let line = "org.mozilla.firefox\tFirefox\t128.0\tstable";
+        assert_eq!(
+            columns(line, "application,name,version,branch").unwrap(),
+            [
+                ("application", "org.mozilla.firefox"),
+                ("name", "Firefox"),
+                ("version", "128.0"),
+                ("branch", "stable"),
+            ]
+        );
+        assert!(columns(line, "application,name").is_err());
+    }
+
+    #[test]
+    fn rows_skip_messages() {
+        let output = "Looking for updates…\n\nNothing to do.\n";
+        assert_eq!(rows(output).count(), 0);
+    }
+
+    #[test]
+    fn package_parses_list_columns() {
+        let line = "org.mozilla.firefox\tFirefox\t128.0\tstable\tFast, Private & Safe Web \
+                    Browser\tsystem\tflathub\t121.5\u{a0}MB";
+        let package = Package::parse(line, LIST_COLUMNS).unwrap();
+        assert_eq!(package.id, "org.mozilla.firefox");
+        assert_eq!(package.name, "Firefox");
+        assert_eq!(package.version, "128.0");
+        assert_eq!(package.branch, "stable");
+        assert_eq!(package.description, "Fast, Private & Safe Web Browser");
+        assert_eq!(package.installation, Some(Installation::System));
+        assert_eq!(package.origin.as_deref(), Some("flathub"));
+        assert_eq!(package.installed_size, Some(121_500_000));
+        assert_eq!(package.installation_flag().as_deref(), Some("--system"));
+    }
+
+    #[test]
+    fn package_takes_the_first_remote_of_search() {
+        let line = "org.gimp.GIMP\tGNU Image Manipulation Program\t2.10.38\tstable\t\
+                    Create images and edit photographs\tflathub,flathub-beta";
+        let package = Package::parse(line, SEARCH_COLUMNS).unwrap();
+        assert_eq!(package.id, "org.gimp.GIMP");
+        assert_eq!(package.origin.as_deref(), Some("flathub"));
+        assert_eq!(package.installation, None);
+
+        let line = "org.gimp.GIMP\tGIMP\t\tstable\t\t";
+        assert_eq!(Package::parse(line, UPDATE_COLUMNS).unwrap().origin, None);
+    }
+
+    #[test]
+    fn remote_parses_installation_among_options() {
+        let remote =
+            Remote::parse("flathub\tFlathub\thttps://dl.flathub.org/repo/\tsystem").unwrap();
+        assert_eq!(remote.name, "flathub");
+        assert_eq!(remote.title.as_deref(), Some("Flathub"));
+        assert_eq!(remote.url, "https://dl.flathub.org/repo/");
+        assert_eq!(remote.installation, Installation::System);
+        assert!(!remote.disabled);
+
+        let remote =
+            Remote::parse("beta\t\thttps://dl.flathub.org/beta-repo/\tuser,disabled").unwrap();
+        assert_eq!(remote.title, None);
+        assert_eq!(remote.installation, Installation::User);
+        assert!(remote.disabled);
+
+        let remote = Remote::parse("local\tLocal\tfile:///srv/repo\tno-gpg-verify,extra").unwrap();
+        assert_eq!(remote.installation, Installation::Custom("extra".into()));
+
+        assert!(Remote::parse("flathub\tFlathub").is_err());
+    }
+
+    #[test]
+    fn installation_from_name() {
+        assert_eq!(Installation::from("user".to_string()), Installation::User);
+        assert_eq!(
+            Installation::from("system".to_string()),
+            Installation::System
+        );
+        assert_eq!(
+            Installation::from("default".to_string()),
+            Installation::System
+        );
+        assert_eq!(
+            Installation::from("steam".to_string()),
+            Installation::Custom("steam".into())
+        );
+        assert_eq!(
+            Installation::Custom("steam".into()).flag(),
+            "--installation=steam"
+        );
+    }
+
+    #[test]
+    fn info_fills_details() {
+        let output = "
+Firefox - Fast, Private & Safe Web Browser
+
+          ID: org.mozilla.firefox
+         Ref: app/org.mozilla.firefox/x86_64/stable
+        Arch: x86_64
+      Branch: stable
+     Version: 128.0
+     License: MPL-2.0
+      Origin: flathub
+  Collection: org.flathub.Stable
+Installation: system
+   Installed: 121.5\u{a0}MB
+     Runtime: org.freedesktop.Platform/x86_64/23.08
+         Sdk: org.freedesktop.Sdk/x86_64/23.08
+";
+        let mut details = PackageDetails::default();
+        parse_info(output, &mut details);
+        assert_eq!(details.name, "Firefox");
+        assert_eq!(
+            details.description.as_deref(),
+            Some("Fast, Private & Safe Web Browser")
+        );
+        assert_eq!(details.version, "128.0");
+        assert_eq!(details.licenses, ["MPL-2.0"]);
+        assert_eq!(details.origin.as_deref(), Some("flathub"));
+        assert_eq!(details.installed_size, Some(121_500_000));
+        assert_eq!(
+            details.dependencies,
+            ["org.freedesktop.Platform/x86_64/23.08"]
+        );
+        assert_eq!(details.extra["id"], "org.mozilla.firefox");
+        assert_eq!(details.extra["branch"], "stable");
+        assert_eq!(details.extra["installation"], "system");
+    }
+}
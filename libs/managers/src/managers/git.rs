@@ -0,0 +1,539 @@
+use super::{report, ErrorKind, ManagerError, PackageDetails, PackageInfo, ProgressEvent};
+use crate::manifest::Entry;
+use crate::pins;
+use crate::utils::command::{self, CommandError};
+use crate::utils::dirs::DIRS;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tokio::sync::mpsc::Sender;
+
+#[derive(Debug)]
+pub enum Error {
+    Fs(std::io::Error),
+    InvalidUrl(String),
+    Parse { line: String },
+    State(serde_json::Error),
+    Command(CommandError),
+    Pins(pins::Error),
+    NoRecipe(String),
+    NoHome,
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fs(err) => write!(f, "File system: {}", err),
+            Self::InvalidUrl(url) => write!(f, "Invalid repository URL: {}", url),
+            Self::Parse { line } => write!(f, "Unexpected output: {}", line),
+            Self::State(err) => write!(f, "Invalid state file: {}", err),
+            Self::Command(err) => write!(f, "Command error: {}", err),
+            Self::Pins(err) => write!(f, "Pins: {}", err),
+            Self::NoRecipe(name) => write!(f, "No recipe found to build {}", name),
+            Self::NoHome => write!(f, "HOME is not set"),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fs(err) => Some(err),
+            Self::State(err) => Some(err),
+            Self::Command(err) => Some(err),
+            Self::Pins(err) => Some(err),
+            Self::InvalidUrl(_) | Self::Parse { .. } | Self::NoRecipe(_) | Self::NoHome => None,
+        }
+    }
+}
+impl ManagerError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Fs(err) => ErrorKind::of_io(err),
+            Self::InvalidUrl(_) | Self::Parse { .. } | Self::State(_) => ErrorKind::Parse,
+            Self::Command(err) => err.kind(),
+            Self::Pins(err) => err.kind(),
+            Self::NoRecipe(_) => ErrorKind::NotFound,
+            Self::NoHome => ErrorKind::Other,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub url: String,
+    pub commit: String,
+}
+impl Package {
+    async fn from_url(url: &str) -> Result<Self, Error> {
+        let name = url
+            .trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .map(|name| name.trim_end_matches(".git"))
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| Error::InvalidUrl(url.into()))?;
+        Ok(Self {
+            name: name.into(),
+            url: url.into(),
+            commit: remote_head(url).await?,
+        })
+    }
+
+    pub fn short_commit(&self) -> &str {
+        &self.commit[..self.commit.len().min(7)]
+    }
+}
+
+impl PackageInfo for Package {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn version(&self) -> &str {
+        self.short_commit()
+    }
+    fn homepage(&self) -> Option<&str> {
+        Some(&self.url)
+    }
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        Some(self.url.as_str().into())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    packages: Vec<Package>,
+}
+impl State {
+    fn path() -> Result<PathBuf, Error> {
+        DIRS.place_data_file("git/state.json").map_err(Error::Fs)
+    }
+
+    fn load() -> Result<Self, Error> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file = std::fs::File::open(path).map_err(Error::Fs)?;
+        serde_json::from_reader(file).map_err(Error::State)
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let file = std::fs::File::create(Self::path()?).map_err(Error::Fs)?;
+        serde_json::to_writer_pretty(file, self).map_err(Error::State)
+    }
+
+    fn upsert(&mut self, package: Package) {
+        self.packages.retain(|p| p.name != package.name);
+        self.packages.push(package);
+    }
+}
+
+/// How to build and install a cloned repository.
+///
+/// A user recipe is looked up in `$XDG_CONFIG_HOME/unipac/git/<name>.sh` (and
+/// `<name>.uninstall.sh`), otherwise the build system is detected from the
+/// repository content.
+enum Recipe {
+    Script {
+        install: PathBuf,
+        uninstall: Option<PathBuf>,
+    },
+    Cargo,
+    Make,
+}
+impl Recipe {
+    fn find(package: &Package, path: &Path) -> Option<Self> {
+        if let Some(install) = DIRS.find_config_file(format!("git/{}.sh", package.name)) {
+            let uninstall = DIRS.find_config_file(format!("git/{}.uninstall.sh", package.name));
+            return Some(Self::Script { install, uninstall });
+        }
+        if path.join("Cargo.toml").exists() {
+            return Some(Self::Cargo);
+        }
+        if path.join("Makefile").exists() {
+            return Some(Self::Make);
+        }
+        None
+    }
+
+    async fn install(&self, path: &Path) -> Result<(), Error> {
+        match self {
+            Self::Script { install, .. } => {
+                run("sh", ["-e".as_ref(), install.as_os_str()], path).await
+            }
+            Self::Cargo => run("cargo", ["install", "--locked", "--path", "."], path).await,
+            Self::Make => {
+                run("make", [prefix()?], path).await?;
+                run("make", ["install".into(), prefix()?], path).await
+            }
+        }
+    }
+
+    async fn uninstall(&self, path: &Path) -> Result<(), Error> {
+        match self {
+            Self::Script {
+                uninstall: Some(uninstall),
+                ..
+            } => run("sh", ["-e".as_ref(), uninstall.as_os_str()], path).await,
+            Self::Cargo => run("cargo", ["uninstall".into(), crate_name(path)?], path).await,
+            Self::Make => run("make", ["uninstall".into(), prefix()?], path).await,
+            Self::Script { .. } => Ok(()),
+        }
+    }
+}
+
+/// Name of the crate `cargo install --path` installed from `path`.
+fn crate_name(path: &Path) -> Result<String, Error> {
+    let path = path.join("Cargo.toml");
+    let invalid = || Error::Parse {
+        line: path.display().to_string(),
+    };
+    let manifest = std::fs::read_to_string(&path).map_err(Error::Fs)?;
+    let manifest: toml::Table = toml::from_str(&manifest).map_err(|_| invalid())?;
+    manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_str())
+        .map(Into::into)
+        .ok_or_else(invalid)
+}
+
+fn prefix() -> Result<String, Error> {
+    let home = std::env::var("HOME").map_err(|_| Error::NoHome)?;
+    Ok(format!("PREFIX={}/.local", home))
+}
+
+/// Runs a command on the blocking pool, for the async methods not to hold up
+/// the runtime while git, make or cargo work.
+async fn blocking<T, F>(run: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, CommandError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(run)
+        .await
+        .expect("Command task panicked")
+        .map_err(Error::Command)
+}
+
+async fn run<I, S>(program: &'static str, args: I, path: &Path) -> Result<(), Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new(program);
+    command.args(args).current_dir(path).stdout(Stdio::null());
+    blocking(move || command::run(&mut command)).await
+}
+
+async fn git<I, S>(args: I) -> Result<String, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new("git");
+    command.args(args);
+    blocking(move || command::output(&mut command)).await
+}
+
+async fn remote_head(url: &str) -> Result<String, Error> {
+    let output = git(["ls-remote", url, "HEAD"]).await?;
+    output
+        .split_whitespace()
+        .next()
+        .map(String::from)
+        .ok_or_else(|| Error::Parse {
+            line: output.clone(),
+        })
+}
+
+fn is_url(query: &str) -> bool {
+    query.contains("://") || query.starts_with("git@")
+}
+
+fn clone_path(name: &str) -> Result<PathBuf, Error> {
+    DIRS.create_data_directory("git")
+        .map(|path| path.join(name))
+        .map_err(Error::Fs)
+}
+
+pub struct Git {
+    progress_sender: Option<Sender<ProgressEvent>>,
+}
+impl Git {
+    pub fn new() -> Self {
+        Self {
+            progress_sender: None,
+        }
+    }
+    pub fn with_progress(progress_sender: Sender<ProgressEvent>) -> Self {
+        Self {
+            progress_sender: progress_sender.into(),
+        }
+    }
+
+    /// Fast-forwards the clone of `package` and rebuilds it.
+    async fn pull(&self, state: &mut State, package: &Package) -> Result<(), Error> {
+        let path = clone_path(&package.name)?;
+        git([
+            "-C".as_ref(),
+            path.as_os_str(),
+            "pull".as_ref(),
+            "--quiet".as_ref(),
+            "--ff-only".as_ref(),
+        ])
+        .await?;
+        state.upsert(self.build(package, &path).await?);
+        state.save()
+    }
+
+    async fn build(&self, package: &Package, path: &Path) -> Result<Package, Error> {
+        let recipe =
+            Recipe::find(package, path).ok_or_else(|| Error::NoRecipe(package.name.clone()))?;
+        recipe.install(path).await?;
+        let commit = git([
+            "-C".as_ref(),
+            path.as_os_str(),
+            "rev-parse".as_ref(),
+            "HEAD".as_ref(),
+        ])
+        .await?;
+        Ok(Package {
+            commit: commit.trim().into(),
+            ..package.clone()
+        })
+    }
+}
+impl Default for Git {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl super::Manager for Git {
+    const NAME: &'static str = "git";
+
+    type Package = Package;
+    type Error = Error;
+
+    async fn list(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        State::load().map(|state| state.packages)
+    }
+
+    async fn find(&self, name: &str) -> Result<Option<Self::Package>, Self::Error> {
+        let packages = self.list().await?;
+        let package = packages
+            .into_iter()
+            .find(|p| p.name == name || p.url == name);
+        Ok(package)
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error> {
+        if is_url(query) {
+            return Package::from_url(query).await.map(|package| vec![package]);
+        }
+        let packages = self.list().await?;
+        let packages = packages
+            .into_iter()
+            .filter(|p| p.name.contains(query) || p.url.contains(query))
+            .collect();
+        Ok(packages)
+    }
+
+    async fn search_install(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error> {
+        if !is_url(query) {
+            return Ok(vec![]);
+        }
+        Package::from_url(query).await.map(|package| vec![package])
+    }
+
+    /// Details of an installed repository, completed from its clone.
+    async fn info(&self, name: &str) -> Result<Option<PackageDetails>, Self::Error> {
+        let Some(package) = self.find(name).await? else {
+            let packages = self.search_install(name).await?;
+            return Ok(packages
+                .first()
+                .map(|package| PackageDetails::new(package, false)));
+        };
+        let path = clone_path(&package.name)?;
+        let mut details = PackageDetails::new(&package, true);
+        details.maintainer = git([
+            "-C".as_ref(),
+            path.as_os_str(),
+            "log".as_ref(),
+            "-1".as_ref(),
+            "--format=%an <%ae>".as_ref(),
+        ])
+        .await
+        .ok()
+        .map(|author| author.trim().into());
+        details.extra.insert("commit".into(), package.commit);
+        details
+            .extra
+            .insert("clone".into(), path.display().to_string());
+        Ok(Some(details))
+    }
+
+    /// Repository whose clone `path` is part of.
+    async fn owns(&self, path: &Path) -> Result<Option<Self::Package>, Self::Error> {
+        let Ok(relative) = path.strip_prefix(DIRS.get_data_file("git")) else {
+            return Ok(None);
+        };
+        let Some(name) = relative.iter().next().and_then(|name| name.to_str()) else {
+            return Ok(None);
+        };
+        self.find(name).await
+    }
+
+    fn entry(&self, package: &Self::Package) -> Entry {
+        Entry::new(&package.url).with_version(package.short_commit())
+    }
+
+    async fn resolve(&self, entry: &Entry) -> Result<Option<Self::Package>, Self::Error> {
+        self.search_install(&entry.name)
+            .await
+            .map(|packages| packages.into_iter().next())
+    }
+
+    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
+        let path = clone_path(&package.name)?;
+        if path.exists() {
+            std::fs::remove_dir_all(&path).map_err(Error::Fs)?;
+        }
+        git([
+            "clone".as_ref(),
+            "--quiet".as_ref(),
+            package.url.as_ref(),
+            path.as_os_str(),
+        ])
+        .await?;
+        let package = self.build(package, &path).await?;
+
+        let mut state = State::load()?;
+        state.upsert(package);
+        state.save()
+    }
+
+    async fn uninstall(&self, package: &Self::Package) -> Result<(), Self::Error> {
+        let path = clone_path(&package.name)?;
+        if let Some(recipe) = Recipe::find(package, &path) {
+            recipe.uninstall(&path).await?;
+        }
+        if path.exists() {
+            std::fs::remove_dir_all(&path).map_err(Error::Fs)?;
+        }
+
+        let mut state = State::load()?;
+        state.packages.retain(|p| p.name != package.name);
+        state.save()
+    }
+
+    async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        let handles = self
+            .list()
+            .await?
+            .into_iter()
+            .map(|package| {
+                tokio::spawn(async move {
+                    let commit = remote_head(&package.url).await?;
+                    Ok((commit != package.commit).then_some(Package { commit, ..package }))
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut packages = Vec::with_capacity(handles.len());
+        for handle in handles {
+            packages.extend(handle.await.expect("Update check panicked")?);
+        }
+        pins::filter_updates(Self::NAME, packages).map_err(Error::Pins)
+    }
+
+    async fn count_updates(&self) -> Result<usize, Self::Error> {
+        self.list_updates().await.map(|v| v.len())
+    }
+
+    async fn update(&self) -> Result<(), Self::Error> {
+        let updates = self.list_updates().await?;
+        self.update_packages(&updates.iter().collect::<Vec<_>>())
+            .await
+    }
+
+    async fn update_packages(&self, updates: &[&Self::Package]) -> Result<(), Self::Error> {
+        let mut state = State::load()?;
+        let total = updates.len();
+        report(
+            &self.progress_sender,
+            ProgressEvent::Started { total: Some(total) },
+        )
+        .await;
+        let mut result = Ok(());
+        for (i, package) in updates.iter().enumerate() {
+            let step = ProgressEvent::Step {
+                step: i + 1,
+                total,
+                package: package.name.clone(),
+            };
+            report(&self.progress_sender, step).await;
+            result = self.pull(&mut state, package).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        report(&self.progress_sender, ProgressEvent::Finished).await;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::Manager;
+
+    #[tokio::test]
+    async fn exported_entry_resolves_to_its_version() {
+        let root = tempfile::tempdir().unwrap();
+        for (variable, directory) in [
+            ("XDG_DATA_HOME", "data"),
+            ("XDG_CONFIG_HOME", "config"),
+            ("XDG_CACHE_HOME", "cache"),
+            ("XDG_STATE_HOME", "state"),
+        ] {
+            std::env::set_var(variable, root.path().join(directory));
+        }
+        let repository = root.path().join("repository");
+        std::fs::create_dir(&repository).unwrap();
+        let repository_arg = repository.as_os_str();
+        git([
+            "-C".as_ref(),
+            repository_arg,
+            "init".as_ref(),
+            "--quiet".as_ref(),
+        ])
+        .await
+        .unwrap();
+        git([
+            "-C".as_ref(),
+            repository_arg,
+            "-c".as_ref(),
+            "user.name=unipac".as_ref(),
+            "-c".as_ref(),
+            "user.email=unipac@localhost".as_ref(),
+            "commit".as_ref(),
+            "--allow-empty".as_ref(),
+            "--quiet".as_ref(),
+            "--message=init".as_ref(),
+        ])
+        .await
+        .unwrap();
+
+        let git = Git::new();
+        let url = format!("file://{}", repository.display());
+        let package = git.search_install(&url).await.unwrap().remove(0);
+        let entry = git.entry(&package);
+        let resolved = git.resolve(&entry).await.unwrap().unwrap();
+
+        assert_eq!(entry.name, url);
+        assert_eq!(entry.version.as_deref(), Some(resolved.version()));
+    }
+}
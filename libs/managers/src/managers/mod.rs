@@ -1,4 +1,16 @@
-#![allow(async_fn_in_trait)]
+use crate::history::Change;
+use crate::manifest::Entry;
+use std::borrow::Cow;
+use std::future::Future;
+use std::path::Path;
+
+mod cache;
+mod details;
+mod dynamic;
+mod error;
+mod exports;
+mod plan;
+mod progress;
 
 #[cfg(feature = "aur")]
 pub mod aur;
@@ -6,33 +18,295 @@ pub mod aur;
 pub mod cargo;
 #[cfg(feature = "flatpak")]
 pub mod flatpak;
+#[cfg(feature = "git")]
+pub mod git;
 #[cfg(feature = "pacman")]
 pub mod pacman;
 #[cfg(feature = "snap")]
 pub mod snap;
 
+pub use cache::CacheUsage;
+pub use details::PackageDetails;
+pub use dynamic::{registry, AnyError, AnyPackage, BoxFuture, DynManager};
+pub use error::{ErrorKind, ManagerError};
+pub use exports::Exports;
+pub use plan::{Action, PlanItem};
+#[cfg(any(
+    feature = "pacman",
+    feature = "aur",
+    feature = "flatpak",
+    feature = "snap",
+    feature = "git",
+    feature = "cargo"
+))]
+pub(crate) use progress::report;
+pub use progress::{Phase, ProgressEvent};
+
 #[cfg(feature = "aur")]
 pub use aur::AUR;
 #[cfg(feature = "cargo")]
 pub use cargo::Cargo;
 #[cfg(feature = "flatpak")]
 pub use flatpak::Flatpak;
+#[cfg(feature = "git")]
+pub use git::Git;
 #[cfg(feature = "pacman")]
 pub use pacman::Pacman;
 #[cfg(feature = "snap")]
 pub use snap::Snap;
 
-pub trait Manager {
-    type Package;
-    type Error;
-
-    async fn list(&self) -> Result<Vec<Self::Package>, Self::Error>;
-    async fn find(&self, name: &str) -> Result<Option<Self::Package>, Self::Error>;
-    async fn search(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error>;
-    async fn search_install(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error>;
-    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error>;
-    async fn uninstall(&self, package: &Self::Package) -> Result<(), Self::Error>;
-    async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error>;
-    async fn count_updates(&self) -> Result<usize, Self::Error>;
-    async fn update(&self) -> Result<(), Self::Error>;
+/// Common metadata exposed by the package of every manager.
+pub trait PackageInfo {
+    fn name(&self) -> &str;
+    fn version(&self) -> &str;
+    /// What the manager knows the package by besides its name (flatpak
+    /// application ID, binaries of a crate...).
+    fn identifier(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    fn description(&self) -> Option<&str> {
+        None
+    }
+    fn homepage(&self) -> Option<&str> {
+        None
+    }
+    /// Where the package comes from (repository, remote, publisher...).
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// Installed size in bytes, when the manager knows it.
+    fn installed_size(&self) -> Option<u64> {
+        None
+    }
+}
+
+pub trait Manager: Sync {
+    const NAME: &'static str;
+
+    type Package: PackageInfo + Send + Sync;
+    type Error: ManagerError;
+
+    fn list(&self) -> impl Future<Output = Result<Vec<Self::Package>, Self::Error>> + Send;
+    fn find(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<Self::Package>, Self::Error>> + Send;
+    fn search(
+        &self,
+        query: &str,
+    ) -> impl Future<Output = Result<Vec<Self::Package>, Self::Error>> + Send;
+    fn search_install(
+        &self,
+        query: &str,
+    ) -> impl Future<Output = Result<Vec<Self::Package>, Self::Error>> + Send;
+    fn install(
+        &self,
+        package: &Self::Package,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    fn uninstall(
+        &self,
+        package: &Self::Package,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    /// Installs all of `packages`, in as few transactions as the manager allows.
+    fn install_many(
+        &self,
+        packages: &[&Self::Package],
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async move {
+            for package in packages {
+                self.install(package).await?;
+            }
+            Ok(())
+        }
+    }
+    /// Uninstalls all of `packages`, in as few transactions as the manager allows.
+    fn uninstall_many(
+        &self,
+        packages: &[&Self::Package],
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async move {
+            for package in packages {
+                self.uninstall(package).await?;
+            }
+            Ok(())
+        }
+    }
+    fn list_updates(&self) -> impl Future<Output = Result<Vec<Self::Package>, Self::Error>> + Send;
+    fn count_updates(&self) -> impl Future<Output = Result<usize, Self::Error>> + Send;
+    fn update(&self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    /// Updates only `packages`, as returned by [`Manager::list_updates`].
+    ///
+    /// Installing the new version is enough for most managers.
+    fn update_packages(
+        &self,
+        packages: &[&Self::Package],
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        self.install_many(packages)
+    }
+
+    /// Packages the user asked for, leaving out those pulled in as dependencies.
+    fn list_explicit(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Self::Package>, Self::Error>> + Send {
+        self.list()
+    }
+
+    /// Packages nothing needs anymore, like dependencies left behind by the
+    /// packages that pulled them.
+    fn list_orphans(&self) -> impl Future<Output = Result<Vec<Self::Package>, Self::Error>> + Send {
+        async move { Ok(vec![]) }
+    }
+
+    /// Removes `orphans`, as returned by [`Manager::list_orphans`].
+    fn remove_orphans(
+        &self,
+        orphans: &[&Self::Package],
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        self.uninstall_many(orphans)
+    }
+
+    /// How `package` is written in a manifest.
+    fn entry(&self, package: &Self::Package) -> Entry {
+        Entry::new(package.name()).with_version(package.version())
+    }
+
+    /// Package to install for a manifest entry, if there is one.
+    fn resolve(
+        &self,
+        entry: &Entry,
+    ) -> impl Future<Output = Result<Option<Self::Package>, Self::Error>> + Send {
+        async move {
+            let packages = self.search_install(&entry.name).await?;
+            Ok(packages.into_iter().find(|p| p.name() == entry.name))
+        }
+    }
+
+    /// Detailed metadata about the package called `name`, installed or not.
+    fn info(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<PackageDetails>, Self::Error>> + Send {
+        async move {
+            if let Some(package) = self.find(name).await? {
+                return Ok(Some(PackageDetails::new(&package, true)));
+            }
+            let packages = self.search_install(name).await?;
+            Ok(packages
+                .iter()
+                .find(|p| p.name() == name)
+                .map(|p| PackageDetails::new(p, false)))
+        }
+    }
+
+    /// Installed package the absolute `path` belongs to, if this manager
+    /// installed it.
+    fn owns(
+        &self,
+        _path: &Path,
+    ) -> impl Future<Output = Result<Option<Self::Package>, Self::Error>> + Send {
+        async move { Ok(None) }
+    }
+
+    /// Commands and desktop entries `package` exposes.
+    fn exports(
+        &self,
+        _package: &Self::Package,
+    ) -> impl Future<Output = Result<Exports, Self::Error>> + Send {
+        async move { Ok(Exports::default()) }
+    }
+
+    /// Space taken by the manager's caches, and how much cleaning them while
+    /// keeping the `keep` newest versions of each package would free.
+    fn cache_usage(
+        &self,
+        _keep: usize,
+    ) -> impl Future<Output = Result<CacheUsage, Self::Error>> + Send {
+        async move { Ok(CacheUsage::default()) }
+    }
+
+    /// Cleans the manager's caches, keeping the `keep` newest versions of
+    /// each package, and returns the bytes freed.
+    fn clean_cache(&self, _keep: usize) -> impl Future<Output = Result<u64, Self::Error>> + Send {
+        async move { Ok(0) }
+    }
+
+    /// Holds `package` back with the manager's own mechanism, if it has one,
+    /// so that updates made outside of unipac skip it too.
+    fn set_held(
+        &self,
+        _package: &Self::Package,
+        _held: bool,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async move { Ok(()) }
+    }
+
+    /// Undoes `change`, previously made by this manager.
+    ///
+    /// Returns `false` when the manager has no way to undo it, like when the
+    /// previous version is not available anymore.
+    fn revert(&self, change: &Change) -> impl Future<Output = Result<bool, Self::Error>> + Send {
+        revert(self, change)
+    }
+
+    /// What [`Manager::install`] would do, without doing it.
+    fn plan_install(
+        &self,
+        package: &Self::Package,
+    ) -> impl Future<Output = Result<Vec<PlanItem>, Self::Error>> + Send {
+        let plan = vec![PlanItem::install(package)];
+        async move { Ok(plan) }
+    }
+
+    /// What [`Manager::uninstall`] would do, without doing it.
+    fn plan_uninstall(
+        &self,
+        package: &Self::Package,
+    ) -> impl Future<Output = Result<Vec<PlanItem>, Self::Error>> + Send {
+        let plan = vec![PlanItem::remove(package)];
+        async move { Ok(plan) }
+    }
+
+    /// What [`Manager::update`] would do, without doing it.
+    fn plan_update(&self) -> impl Future<Output = Result<Vec<PlanItem>, Self::Error>> + Send {
+        async move {
+            let updates = self.list_updates().await?;
+            let installed = self.list().await?;
+            let plan = updates
+                .iter()
+                .map(|package| {
+                    let old_version = installed
+                        .iter()
+                        .find(|p| p.name() == package.name())
+                        .map(|p| p.version().to_string());
+                    PlanItem::upgrade(package, old_version)
+                })
+                .collect();
+            Ok(plan)
+        }
+    }
+}
+
+/// Default [`Manager::revert`]: uninstalls what was installed, and installs
+/// back the previous version of what was removed or upgraded when the manager
+/// still offers it.
+pub(crate) async fn revert<M: Manager + ?Sized>(
+    manager: &M,
+    change: &Change,
+) -> Result<bool, M::Error> {
+    if change.action == Action::Install {
+        return match manager.find(&change.package).await? {
+            Some(package) => manager.uninstall(&package).await.map(|_| true),
+            None => Ok(true),
+        };
+    }
+    let Some(version) = &change.old_version else {
+        return Ok(false);
+    };
+    let entry = Entry::new(&change.package).with_version(version);
+    match manager.resolve(&entry).await? {
+        Some(package) if package.version() == version => {
+            manager.install(&package).await.map(|_| true)
+        }
+        _ => Ok(false),
+    }
 }
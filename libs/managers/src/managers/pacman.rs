@@ -1,9 +1,23 @@
-use crate::utils::alpm::Alpm;
+use super::{
+    report, Action, CacheUsage, ErrorKind, Exports, ManagerError, PackageDetails, PackageInfo,
+    PlanItem, ProgressEvent,
+};
+use crate::history::Change;
+use crate::pins::{self, Pins};
+use crate::utils::alpm::{
+    find_archive, find_owner, install_file, orphans, package_details, package_exports,
+    remove_packages, set_progress_callbacks, stale_archives, sync_packages, transaction, Alpm,
+    TransactionError,
+};
+use crate::utils::cache::{self, CacheFiles};
+use crate::utils::command::{self, CommandError};
+use alpm::TransFlag;
 use alpm_utils::DbListExt;
 use glob_match::glob_match;
+use std::borrow::Cow;
 use std::fmt::Display;
-use std::io::{BufRead as _, BufReader};
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::Arc;
 use tempfile::tempdir;
 use tokio::sync::mpsc::Sender;
@@ -14,6 +28,7 @@ pub struct Package {
     pub description: Option<String>,
     pub url: Option<String>,
     pub database: String,
+    pub installed_size: Option<u64>,
 }
 impl From<&alpm::Package> for Package {
     fn from(value: &alpm::Package) -> Self {
@@ -23,6 +38,7 @@ impl From<&alpm::Package> for Package {
             description: value.desc().map(|desc| desc.into()),
             url: value.url().map(|url| url.into()),
             database: value.db().map_or("unknown".into(), |db| db.name().into()),
+            installed_size: u64::try_from(value.isize()).ok(),
         }
     }
 }
@@ -34,70 +50,179 @@ impl From<&alpm::Pkg> for Package {
             description: value.desc().map(|desc| desc.into()),
             url: value.url().map(|url| url.into()),
             database: value.db().map_or("unknown".into(), |db| db.name().into()),
+            installed_size: u64::try_from(value.isize()).ok(),
         }
     }
 }
 
+impl PackageInfo for Package {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn version(&self) -> &str {
+        &self.version
+    }
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    fn homepage(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        Some(self.database.as_str().into())
+    }
+    fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
-    Alpm(Option<alpm::Error>),
+    Alpm(alpm::Error),
     Fs(std::io::Error),
+    Command(CommandError),
+    Pins(pins::Error),
+    Transaction(TransactionError),
 }
 impl From<alpm::Error> for Error {
     fn from(value: alpm::Error) -> Self {
-        Self::Alpm(Some(value))
+        Self::Alpm(value)
+    }
+}
+impl From<TransactionError> for Error {
+    fn from(value: TransactionError) -> Self {
+        Self::Transaction(value)
     }
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Alpm(Some(err)) => write!(f, "Alpm: {}", err),
-            Self::Alpm(None) => write!(f, "Alpm: Unknown error"),
+            Self::Alpm(err) => write!(f, "Alpm: {}", err),
             Self::Fs(err) => write!(f, "File system: {}", err),
+            Self::Command(err) => write!(f, "Command error: {}", err),
+            Self::Pins(err) => write!(f, "Pins: {}", err),
+            Self::Transaction(err) => write!(f, "Transaction failed: {}", err),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Alpm(err) => Some(err),
+            Self::Fs(err) => Some(err),
+            Self::Command(err) => Some(err),
+            Self::Pins(err) => Some(err),
+            Self::Transaction(err) => Some(err),
+        }
+    }
+}
+impl ManagerError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Alpm(err) => crate::utils::alpm::error_kind(err),
+            Self::Fs(err) => ErrorKind::of_io(err),
+            Self::Command(err) => crate::utils::alpm::command_error_kind(err),
+            Self::Pins(err) => err.kind(),
+            Self::Transaction(err) => err.kind(),
         }
     }
 }
 
 pub struct Pacman {
     alpm: Arc<Alpm>,
-    progress_sender: Option<Sender<String>>,
+    progress_sender: Option<Sender<ProgressEvent>>,
 }
 impl Pacman {
     pub fn new() -> Self {
         Self::create(None)
     }
-    pub fn with_progress(progress_sender: Sender<String>) -> Self {
-        let this = Self::create(Some(progress_sender));
-        this
+    pub fn with_progress(progress_sender: Sender<ProgressEvent>) -> Self {
+        Self::create(Some(progress_sender))
     }
 
-    fn create(progress_sender: Option<Sender<String>>) -> Self {
+    fn create(progress_sender: Option<Sender<ProgressEvent>>) -> Self {
         let alpm = Arc::new(Alpm::new());
+        // Progress is reported by the callbacks of libalpm
+        if let Some(progress_sender) = &progress_sender {
+            set_progress_callbacks(&alpm.lock(), progress_sender.clone());
+        }
         Self {
             alpm,
             progress_sender,
         }
     }
-}
-impl super::Manager for Pacman {
-    type Package = Package;
-    type Error = Error;
 
-    async fn list(&self) -> Result<Vec<Self::Package>, Self::Error> {
+    /// Runs `run` on the alpm handle, framed by the events its callbacks
+    /// report in between.
+    async fn with_alpm(
+        &self,
+        total: Option<usize>,
+        run: impl FnOnce(&mut alpm::Alpm) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        report(&self.progress_sender, ProgressEvent::Started { total }).await;
+        let result = run(&mut self.alpm.lock());
+        report(&self.progress_sender, ProgressEvent::Finished).await;
+        result
+    }
+
+    /// Installed repository packages `filter` keeps.
+    fn list_where(&self, filter: impl Fn(&alpm::Package) -> bool) -> Vec<Package> {
         let alpm = self.alpm.lock();
         let syncdbs = alpm.syncdbs();
         let localdb = alpm.localdb();
-        let packages = localdb
+        localdb
             .pkgs()
             .iter()
+            .filter(|pkg| filter(pkg))
             .filter_map(|pkg| {
-                let Ok(pkg) = syncdbs.pkg(pkg.name()) else {
+                let Ok(sync) = syncdbs.pkg(pkg.name()) else {
                     return None;
                 };
-                Some(pkg.into())
+                // Metadata of the repository, but the version that is installed
+                Some(Package {
+                    version: pkg.version().to_string(),
+                    installed_size: u64::try_from(pkg.isize()).ok(),
+                    ..Package::from(sync)
+                })
             })
-            .collect();
-        Ok(packages)
+            .collect()
+    }
+
+    /// Package archives in the pacman cache directories.
+    fn cache_files(&self, keep: usize) -> CacheFiles {
+        let cachedirs = {
+            let alpm = self.alpm.lock();
+            alpm.cachedirs()
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        };
+        CacheFiles {
+            entries: cachedirs
+                .iter()
+                .flat_map(|dir| cache::entries(dir))
+                .collect(),
+            stale: stale_archives(cachedirs.iter().map(PathBuf::as_path), keep),
+        }
+    }
+}
+impl Default for Pacman {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl super::Manager for Pacman {
+    const NAME: &'static str = "pacman";
+
+    type Package = Package;
+    type Error = Error;
+
+    async fn list(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        Ok(self.list_where(|_| true))
+    }
+
+    async fn list_explicit(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        Ok(self.list_where(|pkg| pkg.reason() == alpm::PackageReason::Explicit))
     }
 
     async fn find(&self, name: &str) -> Result<Option<Self::Package>, Self::Error> {
@@ -108,7 +233,7 @@ impl super::Manager for Pacman {
         let Ok(package) = package else {
             return Ok(None);
         };
-        if !syncdbs.pkg(package.name.as_str()).is_ok() {
+        if syncdbs.pkg(package.name.as_str()).is_err() {
             return Ok(None);
         }
         Ok(Some(package))
@@ -144,43 +269,110 @@ impl super::Manager for Pacman {
         Ok(packages)
     }
 
-    /* async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        let mut alpm = self.alpm.lock();
+    /// Details of the local package when installed, of the sync one otherwise,
+    /// with the version of every repository providing it.
+    async fn info(&self, name: &str) -> Result<Option<PackageDetails>, Self::Error> {
+        let alpm = self.alpm.lock();
         let syncdbs = alpm.syncdbs();
-        let pkg = syncdbs.pkg(package.name.as_str())?;
-        let result = {
-            alpm.trans_init(alpm::TransFlag::NONE)?;
-            alpm.trans_add_pkg(pkg).map_err(|err| err.err)?;
-            alpm.trans_prepare().map_err(|(_, err)| err)?;
-            alpm.trans_commit().map_err(|(_, err)| err)?;
-            Ok(())
+        let Ok(pkg) = syncdbs.pkg(name) else {
+            return Ok(None);
         };
-        alpm.trans_release()?;
-        result
-    } */
+        let mut details = match alpm.localdb().pkg(name) {
+            Ok(local) => package_details(local, true),
+            Err(_) => package_details(pkg, false),
+        };
+        details.origin = pkg.db().map(|db| db.name().into());
+        details.available = syncdbs
+            .iter()
+            .filter_map(|db| {
+                let pkg = db.pkg(name).ok()?;
+                Some(format!("{}/{}", db.name(), pkg.version()))
+            })
+            .collect();
+        Ok(Some(details))
+    }
 
-    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("pacman")
-            .args(["--noconfirm", "-S", package.name.as_str()])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Alpm(None))?;
+    async fn list_orphans(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        let alpm = self.alpm.lock();
+        let syncdbs = alpm.syncdbs();
+        let packages = orphans(&alpm)
+            .into_iter()
+            .filter(|pkg| syncdbs.pkg(pkg.name()).is_ok())
+            .map(Package::from)
+            .collect();
+        Ok(packages)
+    }
+
+    async fn exports(&self, package: &Self::Package) -> Result<Exports, Self::Error> {
+        let alpm = self.alpm.lock();
+        let pkg = alpm.localdb().pkg(package.name.as_str())?;
+        Ok(package_exports(pkg))
+    }
+
+    async fn owns(&self, path: &Path) -> Result<Option<Self::Package>, Self::Error> {
+        let alpm = self.alpm.lock();
+        let Some(pkg) = find_owner(&alpm, path) else {
+            return Ok(None);
+        };
+        // Packages missing from the repositories belong to the AUR
+        Ok(alpm.syncdbs().pkg(pkg.name()).is_ok().then(|| pkg.into()))
+    }
 
-        Ok(())
+    /// Old package versions in the pacman cache, like `paccache` reports them.
+    async fn cache_usage(&self, keep: usize) -> Result<CacheUsage, Self::Error> {
+        Ok(self.cache_files(keep).usage())
+    }
+
+    async fn clean_cache(&self, keep: usize) -> Result<u64, Self::Error> {
+        self.cache_files(keep).clean().map_err(Error::Fs)
+    }
+
+    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
+        self.install_many(&[package]).await
     }
 
     async fn uninstall(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("pacman")
-            .args(["--noconfirm", "-R", package.name.as_str()])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Alpm(None))?;
+        self.uninstall_many(&[package]).await
+    }
+
+    async fn install_many(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        self.with_alpm(Some(packages.len()), |alpm| {
+            let names = packages.iter().map(|package| package.name.as_str());
+            Ok(sync_packages(alpm, TransFlag::NONE, names)?)
+        })
+        .await
+    }
+
+    async fn uninstall_many(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        self.with_alpm(Some(packages.len()), |alpm| {
+            let names = packages.iter().map(|package| package.name.as_str());
+            Ok(remove_packages(alpm, names)?)
+        })
+        .await
+    }
 
-        Ok(())
+    /// Installs back the previous version from the pacman cache.
+    async fn revert(&self, change: &Change) -> Result<bool, Self::Error> {
+        let version = match (change.action, &change.old_version) {
+            (Action::Install, _) | (_, None) => return super::revert(self, change).await,
+            (_, Some(version)) => version,
+        };
+        let mut alpm = self.alpm.lock();
+        let cachedirs = alpm
+            .cachedirs()
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        let archive = find_archive(
+            cachedirs.iter().map(PathBuf::as_path),
+            &change.package,
+            version,
+        );
+        let Some(archive) = archive else {
+            return Ok(false);
+        };
+        install_file(&mut alpm, &archive)?;
+        Ok(true)
     }
 
     async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error> {
@@ -191,33 +383,28 @@ impl super::Manager for Pacman {
 
         std::os::unix::fs::symlink("/var/lib/pacman/local", tmp_path.join("local"))
             .map_err(Error::Fs)?;
-        let status = Command::new("fakeroot")
-            .args([
-                "--",
-                "pacman",
-                "-Sy",
-                "--dbpath",
-                tmp_path_str,
-                "--logfile",
-                "/dev/null",
-                "--noconfirm",
-            ])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()
-            .map_err(Error::Fs)?;
-
-        if !status.success() {
-            return Err(Error::Alpm(None));
-        }
+        command::run(Command::new("fakeroot").args([
+            "--",
+            "pacman",
+            "-Sy",
+            "--dbpath",
+            tmp_path_str,
+            "--logfile",
+            "/dev/null",
+            "--noconfirm",
+        ]))
+        .map_err(Error::Command)?;
 
-        let output = Command::new("pacman")
-            .args(["-Qun", "--dbpath", tmp_path_str])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
-            .map_err(|_| Error::Alpm(None))?;
+        // pacman exits with 1 when there is nothing to upgrade
+        let output = match command::output(Command::new("pacman").args([
+            "-Qun",
+            "--dbpath",
+            tmp_path_str,
+        ])) {
+            Ok(output) => output,
+            Err(err) if err.code() == Some(1) => String::new(),
+            Err(err) => return Err(Error::Command(err)),
+        };
 
         let packages = output
             .lines()
@@ -233,56 +420,80 @@ impl super::Manager for Pacman {
             })
             .collect();
 
-        Ok(packages)
+        pins::filter_updates(Self::NAME, packages).map_err(Error::Pins)
     }
 
     async fn count_updates(&self) -> Result<usize, Self::Error> {
         self.list_updates().await.map(|v| v.len())
     }
 
-    async fn update(&self) -> Result<(), Self::Error> {
-        let stdout = Command::new("pacman")
-            .args(["--noconfirm", "-Syu"])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|_| Error::Alpm(None))?
-            .stdout
-            .ok_or(Error::Alpm(None))?;
-
-        if let Some(progress_sender) = &self.progress_sender {
-            let mut count = 0;
-            let mut i = 0;
-            for line in BufReader::new(stdout).lines() {
-                let Ok(line) = line else {
-                    continue;
-                };
+    async fn plan_install(&self, package: &Self::Package) -> Result<Vec<PlanItem>, Self::Error> {
+        let alpm = self.alpm.lock();
+        let pkg = alpm.syncdbs().pkg(package.name.as_str())?;
+        let download_size = u64::try_from(pkg.download_size()).ok();
+        Ok(vec![
+            PlanItem::install(package).with_download_size(download_size)
+        ])
+    }
 
-                if line.contains("Packages ") {
-                    count = line
-                        .split(&['(', ')'][..])
-                        .nth(1)
-                        .and_then(|s| s.parse().ok())
-                        .unwrap_or_else(|| {
-                            eprintln!("Failed to parse package count");
-                            0
-                        });
-                } else if line.contains("upgrading") && count > 0 {
-                    if let Some(package_name) = line.split(" ").nth(1) {
-                        let _ = progress_sender
-                            .send(format!("{}% {}", i * 100 / count, package_name))
-                            .await;
-                    }
+    async fn plan_update(&self) -> Result<Vec<PlanItem>, Self::Error> {
+        let updates = self.list_updates().await?;
+        let alpm = self.alpm.lock();
+        let localdb = alpm.localdb();
+        let syncdbs = alpm.syncdbs();
+        let plan = updates
+            .iter()
+            .map(|package| {
+                let old_version = localdb
+                    .pkg(package.name.as_str())
+                    .ok()
+                    .map(|pkg| pkg.version().to_string());
+                let download_size = syncdbs
+                    .pkg(package.name.as_str())
+                    .ok()
+                    .and_then(|pkg| u64::try_from(pkg.download_size()).ok());
+                PlanItem::upgrade(package, old_version).with_download_size(download_size)
+            })
+            .collect();
+        Ok(plan)
+    }
 
-                    i += 1;
+    async fn update(&self) -> Result<(), Self::Error> {
+        // Pinned packages, unless pinned at the version the repositories have
+        let pins = Pins::load().map_err(Error::Pins)?;
+        let mut held = Vec::new();
+        {
+            let alpm = self.alpm.lock();
+            let syncdbs = alpm.syncdbs();
+            for pin in pins.of(Self::NAME) {
+                let available = syncdbs
+                    .pkg(pin.package.as_str())
+                    .map(|pkg| pkg.version().to_string());
+                if available.ok().is_none_or(|version| pin.holds(&version)) {
+                    held.push(pin.package.clone());
                 }
             }
-            if i < count || count == 0 {
-                return Err(Error::Alpm(None));
-            }
-            let _ = progress_sender.send("100%".into()).await;
         }
+        self.with_alpm(None, |alpm| {
+            alpm.syncdbs_mut().update(false)?;
+            for name in &held {
+                alpm.add_ignorepkg(name.as_str())?;
+            }
+            let result = transaction(alpm, TransFlag::NONE, |alpm| alpm.sync_sysupgrade(false));
+            for name in &held {
+                let _ = alpm.remove_ignorepkg(name.as_str());
+            }
+            Ok(result?)
+        })
+        .await
+    }
 
-        Ok(())
+    async fn update_packages(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        self.with_alpm(Some(packages.len()), |alpm| {
+            alpm.syncdbs_mut().update(false)?;
+            let names = packages.iter().map(|package| package.name.as_str());
+            Ok(sync_packages(alpm, TransFlag::NEEDED, names)?)
+        })
+        .await
     }
 }
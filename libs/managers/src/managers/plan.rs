@@ -0,0 +1,72 @@
+use super::PackageInfo;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Install,
+    Remove,
+    Upgrade,
+    Downgrade,
+}
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Install => write!(f, "install"),
+            Self::Remove => write!(f, "remove"),
+            Self::Upgrade => write!(f, "upgrade"),
+            Self::Downgrade => write!(f, "downgrade"),
+        }
+    }
+}
+
+/// One step of what a transaction would do, as reported by a dry run.
+#[derive(Clone, Debug)]
+pub struct PlanItem {
+    pub action: Action,
+    pub name: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// Bytes to download, when the manager knows it.
+    pub download_size: Option<u64>,
+}
+impl PlanItem {
+    pub fn install<P: PackageInfo>(package: &P) -> Self {
+        Self {
+            action: Action::Install,
+            name: package.name().into(),
+            old_version: None,
+            new_version: Some(package.version().into()),
+            download_size: None,
+        }
+    }
+
+    pub fn remove<P: PackageInfo>(package: &P) -> Self {
+        Self {
+            action: Action::Remove,
+            name: package.name().into(),
+            old_version: Some(package.version().into()),
+            new_version: None,
+            download_size: None,
+        }
+    }
+
+    /// `package` is the new version, `old_version` the installed one.
+    pub fn upgrade<P: PackageInfo>(package: &P, old_version: Option<String>) -> Self {
+        Self {
+            action: Action::Upgrade,
+            name: package.name().into(),
+            old_version,
+            new_version: Some(package.version().into()),
+            download_size: None,
+        }
+    }
+
+    pub fn with_download_size(self, download_size: Option<u64>) -> Self {
+        Self {
+            download_size,
+            ..self
+        }
+    }
+}
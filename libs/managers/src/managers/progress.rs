@@ -0,0 +1,100 @@
+use std::fmt::Display;
+
+/// What a manager reports on its progress channel while it works, so that
+/// frontends can show bars and per-package lines without parsing text.
+#[derive(Clone, Debug)]
+pub enum ProgressEvent {
+    /// The manager started working, on `total` packages when it knows how many.
+    Started { total: Option<usize> },
+    /// The manager moved on to another phase of its work.
+    Phase(Phase),
+    /// Step `step` out of `total` of the current phase, working on `package`.
+    Step {
+        step: usize,
+        total: usize,
+        package: String,
+    },
+    /// Bytes of `file` downloaded so far, out of `total` when it is known.
+    Bytes {
+        file: String,
+        done: u64,
+        total: Option<u64>,
+    },
+    /// A line printed by the tool the manager runs.
+    Log(String),
+    /// Something went wrong without stopping the manager.
+    Warning(String),
+    /// The manager is done, whether it succeeded or not.
+    Finished,
+}
+impl Display for ProgressEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Started { total: Some(total) } => write!(f, "started on {} packages", total),
+            Self::Started { total: None } => write!(f, "started"),
+            Self::Phase(phase) => write!(f, "{}", phase),
+            Self::Step {
+                step,
+                total,
+                package,
+            } => write!(f, "({}/{}) {}", step, total, package),
+            Self::Bytes { file, done, total } => match total {
+                Some(total) if *total > 0 => write!(f, "{}% {}", done * 100 / total, file),
+                _ => write!(f, "{}", file),
+            },
+            Self::Log(line) => write!(f, "{}", line),
+            Self::Warning(warning) => write!(f, "warning: {}", warning),
+            Self::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+/// Phase of a manager's work, in the order they usually come in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Refreshing the package databases.
+    Refreshing,
+    /// Resolving dependencies and looking for conflicts.
+    Resolving,
+    Downloading,
+    /// Checking the integrity, signatures and file conflicts of packages.
+    Checking,
+    Building,
+    Installing,
+    Removing,
+    /// Running the hooks that follow a transaction.
+    Hooks,
+}
+impl Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Refreshing => write!(f, "refreshing databases"),
+            Self::Resolving => write!(f, "resolving dependencies"),
+            Self::Downloading => write!(f, "downloading"),
+            Self::Checking => write!(f, "checking packages"),
+            Self::Building => write!(f, "building"),
+            Self::Installing => write!(f, "installing"),
+            Self::Removing => write!(f, "removing"),
+            Self::Hooks => write!(f, "running hooks"),
+        }
+    }
+}
+
+/// Sends `event` when the manager has a progress channel, a frontend that
+/// stopped listening not being an error.
+#[cfg(any(
+    feature = "pacman",
+    feature = "aur",
+    feature = "flatpak",
+    feature = "snap",
+    feature = "git",
+    feature = "cargo"
+))]
+pub(crate) async fn report(
+    sender: &Option<tokio::sync::mpsc::Sender<ProgressEvent>>,
+    event: ProgressEvent,
+) {
+    if let Some(sender) = sender {
+        let _ = sender.send(event).await;
+    }
+}
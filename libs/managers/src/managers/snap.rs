@@ -1,26 +1,68 @@
-use once_cell::sync::Lazy;
-use regex::Regex;
+use super::{
+    report, Action, CacheUsage, ErrorKind, Exports, ManagerError, PackageDetails, PackageInfo,
+    ProgressEvent,
+};
+use crate::history::Change;
+use crate::pins;
+use crate::utils::cache::{self, CacheFiles};
+use crate::utils::config;
+use crate::utils::snapd::{self, encode, Client, Confinement, SnapdError, Validation};
+use serde::Deserialize;
+use serde_json::json;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::process::{Command, Stdio};
-use std::str::FromStr;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 use tokio::sync::mpsc::Sender;
 
-static SEPARATOR_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"  +").unwrap());
-
-fn get_next<'a>(parts: &mut impl Iterator<Item = &'a str>) -> Result<String, Error> {
-    parts.next().map(String::from).ok_or(Error::Format)
-}
-
 #[derive(Debug)]
 pub enum Error {
-    Format,
-    Command,
+    Snapd(SnapdError),
+    Pins(pins::Error),
+    /// The publisher of the snap is not verified, as the configuration requires.
+    UnverifiedPublisher {
+        package: String,
+        publisher: String,
+    },
+    Fs(std::io::Error),
+}
+impl From<SnapdError> for Error {
+    fn from(value: SnapdError) -> Self {
+        Self::Snapd(value)
+    }
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Format => write!(f, "Format error"),
-            Self::Command => write!(f, "Command error"),
+            Self::Snapd(err) => write!(f, "{}", err),
+            Self::Pins(err) => write!(f, "Pins: {}", err),
+            Self::UnverifiedPublisher { package, publisher } => write!(
+                f,
+                "{} is published by {}, who is not verified",
+                package, publisher
+            ),
+            Self::Fs(err) => write!(f, "File system: {}", err),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Snapd(err) => Some(err),
+            Self::Pins(err) => Some(err),
+            Self::UnverifiedPublisher { .. } => None,
+            Self::Fs(err) => Some(err),
+        }
+    }
+}
+impl ManagerError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Snapd(err) => err.kind(),
+            Self::Pins(err) => err.kind(),
+            Self::UnverifiedPublisher { .. } => ErrorKind::PermissionDenied,
+            Self::Fs(err) => ErrorKind::of_io(err),
         }
     }
 }
@@ -29,112 +71,355 @@ pub struct Package {
     pub name: String,
     pub version: String,
     pub description: String,
+    /// Store username of the publisher.
     pub publisher: String,
+    pub validation: Validation,
+    /// Channel the snap tracks, or the store offers it in.
+    pub channel: Option<String>,
+    pub confinement: Confinement,
+    pub revision: Option<String>,
+    pub installed_size: Option<u64>,
 }
-impl FromStr for Package {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = SEPARATOR_REGEX.split(s);
-        let name = get_next(&mut parts)?;
-        let version = get_next(&mut parts)?;
-        parts.next();
-        let description = get_next(&mut parts)?;
-        Ok(Package {
-            name,
-            version,
-            description,
-            publisher: String::new(),
-        })
+impl From<snapd::Snap> for Package {
+    fn from(value: snapd::Snap) -> Self {
+        let (publisher, validation) = value.publisher.map_or_else(Default::default, |publisher| {
+            (publisher.username, publisher.validation)
+        });
+        Self {
+            name: value.name,
+            version: value.version,
+            description: value.summary,
+            publisher,
+            validation,
+            channel: value.tracking_channel.or(value.channel),
+            confinement: value.confinement,
+            revision: (!value.revision.is_empty()).then_some(value.revision),
+            installed_size: value.installed_size,
+        }
+    }
+}
+
+impl PackageInfo for Package {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn version(&self) -> &str {
+        &self.version
+    }
+    fn description(&self) -> Option<&str> {
+        Some(&self.description)
+    }
+    fn origin(&self) -> Option<Cow<'_, str>> {
+        (!self.publisher.is_empty()).then(|| self.publisher.as_str().into())
+    }
+    fn installed_size(&self) -> Option<u64> {
+        self.installed_size
     }
 }
 
-pub struct Snap {}
+/// Details of `snap`, with the channels the `store` offers it in.
+fn details(snap: snapd::Snap, installed: bool, store: Option<snapd::Snap>) -> PackageDetails {
+    let mut extra = BTreeMap::new();
+    extra.insert("confinement".into(), snap.confinement.to_string());
+    if !snap.revision.is_empty() {
+        extra.insert("revision".into(), snap.revision);
+    }
+    if let Some(publisher) = &snap.publisher {
+        extra.insert("publisher".into(), publisher.validation.to_string());
+    }
+    let available = store
+        .map(|store| store.channels)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(channel, offered)| format!("{} {}", channel, offered.version))
+        .collect();
+    PackageDetails {
+        name: snap.name,
+        version: snap.version,
+        installed,
+        description: Some(snap.summary).filter(|summary| !summary.is_empty()),
+        homepage: snap.website.or(snap.store_url),
+        origin: snap.tracking_channel.or(snap.channel),
+        maintainer: snap.publisher.map(|publisher| publisher.display_name),
+        licenses: snap
+            .license
+            .into_iter()
+            .filter(|license| license != "unset")
+            .collect(),
+        installed_size: snap.installed_size.filter(|_| installed),
+        install_date: snap.install_date,
+        available,
+        extra,
+        ..PackageDetails::default()
+    }
+}
+
+/// Downloads snapd keeps in `/var/lib/snapd/cache`, those no installed
+/// revision is hard-linked to anymore being stale.
+fn cache_files() -> CacheFiles {
+    let entries = cache::entries(Path::new("/var/lib/snapd/cache"));
+    let stale = entries
+        .iter()
+        .filter(|path| path.metadata().is_ok_and(|metadata| metadata.nlink() == 1))
+        .cloned()
+        .collect();
+    CacheFiles { entries, stale }
+}
+
+/// `[snap]` table of the configuration file.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Socket of the snapd REST API.
+    pub socket: PathBuf,
+    /// Refuse to install snaps whose publisher the store did not verify.
+    pub verified_only: bool,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            socket: snapd::SOCKET.into(),
+            verified_only: false,
+        }
+    }
+}
+
+pub struct Snap {
+    client: Client,
+    progress_sender: Option<Sender<ProgressEvent>>,
+    options: Options,
+}
 impl Snap {
     pub fn new() -> Self {
-        Self {}
+        Self::create(None)
+    }
+    pub fn with_progress(progress_sender: Sender<ProgressEvent>) -> Self {
+        Self::create(Some(progress_sender))
+    }
+
+    fn create(progress_sender: Option<Sender<ProgressEvent>>) -> Self {
+        let options: Options = config::section("snap");
+        Self {
+            client: Client::new(&options.socket),
+            progress_sender,
+            options,
+        }
+    }
+
+    /// Asks snapd for `action` on `path`, waiting for the change it starts.
+    async fn act(&self, path: &str, action: serde_json::Value) -> Result<(), Error> {
+        if let Some(change) = self.client.post(path, &action).await? {
+            self.client.wait(&change, &self.progress_sender).await?;
+        }
+        Ok(())
+    }
+
+    /// Refreshes `snaps`, or all of them when empty.
+    async fn refresh(&self, snaps: Vec<&str>) -> Result<(), Error> {
+        let total = (!snaps.is_empty()).then_some(snaps.len());
+        report(&self.progress_sender, ProgressEvent::Started { total }).await;
+        let result = self
+            .act("/v2/snaps", json!({ "action": "refresh", "snaps": snaps }))
+            .await;
+        report(&self.progress_sender, ProgressEvent::Finished).await;
+        result
     }
-    pub fn with_progress(_progress_sender: Sender<String>) -> Self {
-        Self {}
+
+    /// Installed snap named `name`, disabled revisions aside.
+    async fn installed(&self, name: &str) -> Result<Option<snapd::Snap>, Error> {
+        let path = format!("/v2/snaps/{}", encode(name));
+        Ok(self.client.get_optional(&path).await?)
+    }
+
+    /// Snaps of the store, looked up by `name` or by a search `query`.
+    async fn store(&self, query: &str, exact: bool) -> Result<Vec<snapd::Snap>, Error> {
+        let parameter = if exact { "name" } else { "q" };
+        let path = format!("/v2/find?{}={}", parameter, encode(query));
+        Ok(self.client.get_optional(&path).await?.unwrap_or_default())
+    }
+}
+impl Default for Snap {
+    fn default() -> Self {
+        Self::new()
     }
 }
 impl super::Manager for Snap {
+    const NAME: &'static str = "snap";
+
     type Package = Package;
     type Error = Error;
 
     async fn list(&self) -> Result<Vec<Self::Package>, Self::Error> {
-        Command::new("snap")
-            .arg("list")
-            .output()
-            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
-            .map_err(|_| Error::Command)?
-            .lines()
-            .skip(1)
-            .map(Package::from_str)
-            .collect()
+        let snaps: Vec<snapd::Snap> = self.client.get("/v2/snaps").await?;
+        Ok(snaps.into_iter().map(Package::from).collect())
     }
 
     async fn find(&self, name: &str) -> Result<Option<Self::Package>, Self::Error> {
-        let packages = self.list().await?;
-        let package = packages
-            .into_iter()
-            .find(|p| p.name.to_lowercase() == name.to_lowercase());
-        Ok(package)
+        let snap = self.installed(&name.to_lowercase()).await?;
+        Ok(snap.map(Package::from))
     }
 
     async fn search(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error> {
-        Command::new("snap")
-            .args(["find", query])
-            .output()
-            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
-            .map_err(|_| Error::Command)?
-            .lines()
-            .skip(1)
-            .filter_map(|s| s.contains("  ").then(|| Package::from_str(s)))
-            .collect()
+        let snaps = self.store(query, false).await?;
+        Ok(snaps.into_iter().map(Package::from).collect())
     }
 
     async fn search_install(&self, query: &str) -> Result<Vec<Self::Package>, Self::Error> {
-        let pkgs = self.search(query).await?;
-        let pkgs = pkgs
+        let snaps = self.store(query, true).await?;
+        Ok(snaps.into_iter().map(Package::from).collect())
+    }
+
+    async fn info(&self, name: &str) -> Result<Option<PackageDetails>, Self::Error> {
+        let installed = self.installed(name).await?;
+        // Snaps installed from a file are unknown to the store, which may
+        // also be out of reach
+        let store = match self.store(name, true).await {
+            Ok(snaps) => snaps.into_iter().next(),
+            Err(_) if installed.is_some() => None,
+            Err(err) => return Err(err),
+        };
+        Ok(match (installed, store) {
+            (Some(snap), store) => Some(details(snap, true, store)),
+            (None, Some(snap)) => Some(details(snap.clone(), false, Some(snap))),
+            (None, None) => None,
+        })
+    }
+
+    /// Disabled revisions snapd keeps around to revert refreshes.
+    async fn list_orphans(&self) -> Result<Vec<Self::Package>, Self::Error> {
+        let snaps: Vec<snapd::Snap> = self.client.get("/v2/snaps?select=all").await?;
+        Ok(snaps
             .into_iter()
-            .find(|p| p.name == query)
-            .map_or_else(|| vec![], |pkg| vec![pkg]);
-        Ok(pkgs)
+            .filter(|snap| snap.status == "installed")
+            .map(Package::from)
+            .collect())
     }
 
-    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("snap")
-            .args(["install", &package.name])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Command)?;
+    async fn remove_orphans(&self, orphans: &[&Self::Package]) -> Result<(), Self::Error> {
+        for orphan in orphans {
+            // Without a revision, the whole snap would be removed
+            let Some(revision) = &orphan.revision else {
+                continue;
+            };
+            let path = format!("/v2/snaps/{}", encode(&orphan.name));
+            self.act(&path, json!({ "action": "remove", "revision": revision }))
+                .await?;
+        }
         Ok(())
     }
 
+    /// Commands in `/snap/bin` and desktop entries snapd generated for the snap.
+    async fn exports(&self, package: &Self::Package) -> Result<Exports, Self::Error> {
+        let entries = |dir: &str| {
+            std::fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+        };
+        // Commands are named `<snap>` or `<snap>.<app>`
+        let app_prefix = format!("{}.", package.name);
+        let bins = entries("/snap/bin")
+            .filter(|bin| {
+                bin.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name == package.name || name.starts_with(&app_prefix))
+            })
+            .collect();
+        // Desktop entries are named `<snap>_<app>.desktop`
+        let entry_prefix = format!("{}_", package.name);
+        let desktop_entries = entries("/var/lib/snapd/desktop/applications")
+            .filter_map(|entry| {
+                let name = entry.file_name()?.to_str()?;
+                let app = name.strip_prefix(&entry_prefix)?.strip_suffix(".desktop")?;
+                Some(app.to_string())
+            })
+            .collect();
+        Ok(Exports {
+            bins,
+            desktop_entries,
+        })
+    }
+
+    /// Snap of a command in `/snap/bin`, or of a file of its `/snap/<name>` mount.
+    async fn owns(&self, path: &Path) -> Result<Option<Self::Package>, Self::Error> {
+        let name = if let Ok(command) = path.strip_prefix("/snap/bin") {
+            // Commands are named `<snap>` or `<snap>.<app>`
+            command
+                .to_str()
+                .and_then(|command| command.split('.').next())
+        } else if let Ok(file) = path.strip_prefix("/snap") {
+            file.iter().next().and_then(|name| name.to_str())
+        } else {
+            None
+        };
+        match name {
+            Some(name) => self.find(name).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn cache_usage(&self, _keep: usize) -> Result<CacheUsage, Self::Error> {
+        Ok(cache_files().usage())
+    }
+
+    async fn clean_cache(&self, _keep: usize) -> Result<u64, Self::Error> {
+        cache_files().clean().map_err(Error::Fs)
+    }
+
+    /// Installs `package` from the channel and with the confinement the
+    /// store offers it in.
+    async fn install(&self, package: &Self::Package) -> Result<(), Self::Error> {
+        if self.options.verified_only && package.validation == Validation::Unproven {
+            return Err(Error::UnverifiedPublisher {
+                package: package.name.clone(),
+                publisher: package.publisher.clone(),
+            });
+        }
+        let path = format!("/v2/snaps/{}", encode(&package.name));
+        let action = json!({
+            "action": "install",
+            "channel": package.channel,
+            "classic": package.confinement == Confinement::Classic,
+            "devmode": package.confinement == Confinement::Devmode,
+        });
+        self.act(&path, action).await
+    }
+
     async fn uninstall(&self, package: &Self::Package) -> Result<(), Self::Error> {
-        Command::new("snap")
-            .args(["remove", &package.name])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Command)?;
-        Ok(())
+        let path = format!("/v2/snaps/{}", encode(&package.name));
+        self.act(&path, json!({ "action": "remove" })).await
     }
 
+    async fn uninstall_many(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        let snaps = packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>();
+        self.act("/v2/snaps", json!({ "action": "remove", "snaps": snaps }))
+            .await
+    }
+
+    /// Goes back to the revision snapd kept from before the refresh.
+    async fn revert(&self, change: &Change) -> Result<bool, Self::Error> {
+        if change.action != Action::Upgrade {
+            return super::revert(self, change).await;
+        }
+        let path = format!("/v2/snaps/{}", encode(&change.package));
+        self.act(&path, json!({ "action": "revert" })).await?;
+        Ok(true)
+    }
+
+    /// Snaps the store has a newer revision of, in the channel they track.
     async fn list_updates(&self) -> Result<Vec<Self::Package>, Self::Error> {
-        Command::new("snap")
-            .args(["refresh", "--list"])
-            .output()
-            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
-            .map_err(|_| Error::Command)?
-            .lines()
-            .skip(1)
-            .filter_map(|s| s.contains("  ").then(|| Package::from_str(s)))
-            .collect()
+        let snaps: Option<Vec<snapd::Snap>> =
+            self.client.get_optional("/v2/find?select=refresh").await?;
+        let packages = snaps
+            .unwrap_or_default()
+            .into_iter()
+            .map(Package::from)
+            .collect();
+        pins::filter_updates(Self::NAME, packages).map_err(Error::Pins)
     }
 
     async fn count_updates(&self) -> Result<usize, Self::Error> {
@@ -142,13 +427,28 @@ impl super::Manager for Snap {
     }
 
     async fn update(&self) -> Result<(), Self::Error> {
-        Command::new("snap")
-            .arg("refresh")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut p| p.wait())
-            .map_err(|_| Error::Command)?;
-        Ok(())
+        self.refresh(Vec::new()).await
+    }
+
+    async fn set_held(&self, package: &Self::Package, held: bool) -> Result<(), Self::Error> {
+        let action = if held {
+            json!({
+                "action": "hold",
+                "snaps": [package.name],
+                "time": "forever",
+                "hold-level": "general",
+            })
+        } else {
+            json!({ "action": "unhold", "snaps": [package.name] })
+        };
+        self.act("/v2/snaps", action).await
+    }
+
+    async fn update_packages(&self, packages: &[&Self::Package]) -> Result<(), Self::Error> {
+        let snaps = packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect();
+        self.refresh(snaps).await
     }
 }
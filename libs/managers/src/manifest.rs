@@ -0,0 +1,190 @@
+use crate::managers::ErrorKind;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Packages a machine should have, as written in `unipac.toml`.
+///
+/// ```toml
+/// [pacman]
+/// packages = ["git", { name = "firefox", version = "128.0-1" }]
+///
+/// [flatpak]
+/// remotes = { flathub = "https://dl.flathub.org/repo/flathub.flatpakrepo" }
+/// packages = ["org.mozilla.firefox"]
+///
+/// [cargo]
+/// packages = [{ name = "ripgrep", features = ["pcre2"] }]
+/// ```
+#[derive(Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(flatten)]
+    pub managers: BTreeMap<String, Section>,
+}
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path).map_err(|err| Error::Read(path.into(), err))?;
+        toml::from_str(&content).map_err(|err| Error::Parse(path.into(), err))
+    }
+
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string_pretty(self).map_err(Error::Serialize)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, self.to_toml()?).map_err(|err| Error::Write(path.into(), err))
+    }
+}
+
+/// What a manifest asks from one manager.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Section {
+    /// Remotes to add before installing, by name (flatpak only).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub remotes: BTreeMap<String, String>,
+    pub packages: Vec<Entry>,
+}
+impl Section {
+    /// Compares the wanted packages with the `installed` ones.
+    pub fn diff(&self, installed: &[Entry]) -> Diff {
+        let mut diff = Diff::default();
+        for entry in &self.packages {
+            match installed.iter().find(|i| i.name == entry.name) {
+                None => diff.missing.push(entry.clone()),
+                Some(installed) => {
+                    if entry
+                        .version
+                        .as_ref()
+                        .is_some_and(|version| installed.version.as_ref() != Some(version))
+                    {
+                        diff.mismatched.push((entry.clone(), installed.clone()));
+                    }
+                }
+            }
+        }
+        diff.extra = installed
+            .iter()
+            .filter(|i| !self.packages.iter().any(|entry| entry.name == i.name))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+/// A package of a manifest.
+///
+/// Written as a bare name unless it has a version or features.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "RawEntry", into = "RawEntry")]
+pub struct Entry {
+    pub name: String,
+    /// Version the package is pinned to.
+    pub version: Option<String>,
+    /// Features to build the package with (cargo only).
+    pub features: Vec<String>,
+}
+impl Entry {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: None,
+            features: vec![],
+        }
+    }
+
+    pub fn with_version(self, version: impl Into<String>) -> Self {
+        Self {
+            version: Some(version.into()),
+            ..self
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawEntry {
+    Name(String),
+    Detailed {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        features: Vec<String>,
+    },
+}
+impl From<RawEntry> for Entry {
+    fn from(raw: RawEntry) -> Self {
+        match raw {
+            RawEntry::Name(name) => Self::new(name),
+            RawEntry::Detailed {
+                name,
+                version,
+                features,
+            } => Self {
+                name,
+                version,
+                features,
+            },
+        }
+    }
+}
+impl From<Entry> for RawEntry {
+    fn from(entry: Entry) -> Self {
+        if entry.version.is_none() && entry.features.is_empty() {
+            return Self::Name(entry.name);
+        }
+        Self::Detailed {
+            name: entry.name,
+            version: entry.version,
+            features: entry.features,
+        }
+    }
+}
+
+/// Difference between a manifest section and what is installed.
+#[derive(Default)]
+pub struct Diff {
+    /// Wanted but not installed.
+    pub missing: Vec<Entry>,
+    /// Installed with another version than the pinned one, with the installed entry.
+    pub mismatched: Vec<(Entry, Entry)>,
+    /// Installed but not wanted.
+    pub extra: Vec<Entry>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Write(PathBuf, std::io::Error),
+    Serialize(toml::ser::Error),
+}
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Read(_, err) | Self::Write(_, err) => ErrorKind::of_io(err),
+            Self::Parse(..) | Self::Serialize(_) => ErrorKind::Parse,
+        }
+    }
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            Self::Parse(path, err) => write!(f, "Invalid manifest {}: {}", path.display(), err),
+            Self::Write(path, err) => write!(f, "Failed to write {}: {}", path.display(), err),
+            Self::Serialize(err) => write!(f, "Failed to serialize manifest: {}", err),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read(_, err) | Self::Write(_, err) => Some(err),
+            Self::Parse(_, err) => Some(err),
+            Self::Serialize(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::managers::{ErrorKind, PackageInfo};
+use crate::utils::dirs::DIRS;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// A package unipac keeps from being updated.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pin {
+    pub manager: String,
+    pub package: String,
+    /// Version the package is held at, any update being held back when there is none.
+    pub version: Option<String>,
+}
+impl Pin {
+    /// Whether an update to `version` is held back.
+    pub fn holds(&self, version: &str) -> bool {
+        self.version.as_deref() != Some(version)
+    }
+}
+
+/// Pinned packages, as stored in `$XDG_DATA_HOME/unipac/pins.json`.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Pins {
+    pub pins: Vec<Pin>,
+}
+impl Pins {
+    pub fn path() -> Result<PathBuf, Error> {
+        DIRS.place_data_file("pins.json").map_err(Error::Fs)
+    }
+
+    pub fn load() -> Result<Self, Error> {
+        let file = match std::fs::File::open(Self::path()?) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Fs(err)),
+        };
+        serde_json::from_reader(file).map_err(Error::Parse)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let file = std::fs::File::create(Self::path()?).map_err(Error::Fs)?;
+        serde_json::to_writer_pretty(file, self).map_err(Error::Parse)
+    }
+
+    pub fn get(&self, manager: &str, package: &str) -> Option<&Pin> {
+        self.pins
+            .iter()
+            .find(|pin| pin.manager == manager && pin.package == package)
+    }
+
+    /// Adds `pin`, replacing the previous pin of the same package.
+    pub fn insert(&mut self, pin: Pin) {
+        self.remove(&pin.manager, &pin.package);
+        self.pins.push(pin);
+    }
+
+    pub fn remove(&mut self, manager: &str, package: &str) -> Option<Pin> {
+        let index = self
+            .pins
+            .iter()
+            .position(|pin| pin.manager == manager && pin.package == package)?;
+        Some(self.pins.remove(index))
+    }
+
+    /// Pins of `manager`.
+    pub fn of<'a>(&'a self, manager: &'a str) -> impl Iterator<Item = &'a Pin> {
+        self.pins.iter().filter(move |pin| pin.manager == manager)
+    }
+}
+
+/// Drops the `updates` of `manager` held back by a pin.
+pub fn filter_updates<P>(manager: &str, mut updates: Vec<P>) -> Result<Vec<P>, Error>
+where
+    P: PackageInfo,
+{
+    let pins = Pins::load()?;
+    updates.retain(|package| {
+        pins.get(manager, package.name())
+            .is_none_or(|pin| !pin.holds(package.version()))
+    });
+    Ok(updates)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Fs(std::io::Error),
+    Parse(serde_json::Error),
+}
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Fs(err) => ErrorKind::of_io(err),
+            Self::Parse(_) => ErrorKind::Parse,
+        }
+    }
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fs(err) => write!(f, "Filesystem error: {}", err),
+            Self::Parse(err) => write!(f, "Invalid pins file: {}", err),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fs(err) => Some(err),
+            Self::Parse(err) => Some(err),
+        }
+    }
+}
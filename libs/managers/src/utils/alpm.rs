@@ -1,5 +1,18 @@
-use alpm_utils::alpm_with_conf;
+use crate::managers::{ErrorKind, Exports, PackageDetails, Phase, ProgressEvent};
+use crate::utils::cache;
+use crate::utils::command::CommandError;
+use alpm::{
+    CommitData, CommitError, DownloadEvent, Event, LogLevel, PrepareData, PrepareError, Progress,
+    TransFlag,
+};
+use alpm_utils::{alpm_with_conf, DbListExt as _};
+use chrono::{Local, TimeZone};
 use pacmanconf::Config;
+use std::fmt::Display;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
 
 pub struct Alpm {
     inner: std::sync::Mutex<alpm::Alpm>,
@@ -12,13 +25,444 @@ impl Alpm {
     pub fn new() -> Self {
         let config = Config::new().expect("Failed to load pacman config");
         let inner = alpm_with_conf(&config).expect("Failed to initialize alpm");
-        let alpm = Self {
+        Self {
             inner: inner.into(),
-        };
-        alpm
+        }
     }
 
     pub fn lock(&self) -> std::sync::MutexGuard<'_, alpm::Alpm> {
         self.inner.lock().unwrap()
     }
 }
+impl Default for Alpm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Details of `pkg`, `installed` telling whether it comes from the local database.
+pub fn package_details(pkg: &alpm::Pkg, installed: bool) -> PackageDetails {
+    PackageDetails {
+        name: pkg.name().into(),
+        version: pkg.version().to_string(),
+        installed,
+        description: pkg.desc().map(String::from),
+        homepage: pkg.url().map(String::from),
+        origin: pkg.db().map(|db| db.name().into()),
+        maintainer: pkg.packager().map(String::from),
+        licenses: pkg.licenses().iter().map(String::from).collect(),
+        dependencies: pkg.depends().iter().map(|dep| dep.to_string()).collect(),
+        installed_size: u64::try_from(pkg.isize()).ok(),
+        install_date: pkg
+            .install_date()
+            .and_then(|date| Local.timestamp_opt(date, 0).single()),
+        ..PackageDetails::default()
+    }
+}
+
+/// Local package whose file list contains the absolute `path`.
+pub fn find_owner<'a>(alpm: &'a alpm::Alpm, path: &Path) -> Option<&'a alpm::Package> {
+    let path = path.strip_prefix("/").ok()?.to_str()?;
+    alpm.localdb()
+        .pkgs()
+        .iter()
+        .find(|pkg| pkg.files().files().iter().any(|file| file.name() == path))
+}
+
+/// Executables and desktop entries in the file list of `pkg`.
+pub fn package_exports(pkg: &alpm::Pkg) -> Exports {
+    let mut exports = Exports::default();
+    for file in pkg.files().files() {
+        let name = file.name();
+        if let Some(bin) = name
+            .strip_prefix("usr/bin/")
+            .filter(|bin| !bin.is_empty() && !bin.contains('/'))
+        {
+            exports.bins.push(Path::new("/usr/bin").join(bin));
+        } else if let Some(entry) = name
+            .strip_prefix("usr/share/applications/")
+            .and_then(|entry| entry.strip_suffix(".desktop"))
+        {
+            exports.desktop_entries.push(entry.into());
+        }
+    }
+    exports
+}
+
+/// Packages installed as dependencies that nothing needs anymore, including
+/// the ones only needed by other orphans.
+pub fn orphans(alpm: &alpm::Alpm) -> Vec<&alpm::Package> {
+    let mut orphans: Vec<&alpm::Package> = Vec::new();
+    loop {
+        let is_orphan = |name: &str| orphans.iter().any(|orphan| orphan.name() == name);
+        let found = alpm
+            .localdb()
+            .pkgs()
+            .iter()
+            .filter(|pkg| {
+                pkg.reason() == alpm::PackageReason::Depend
+                    && !is_orphan(pkg.name())
+                    && pkg.required_by().iter().all(is_orphan)
+                    && pkg.optional_for().iter().all(is_orphan)
+            })
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            return orphans;
+        }
+        orphans.extend(found);
+    }
+}
+
+/// Package archive of `name` at `version` in one of `dirs`, as left there by
+/// pacman or makepkg.
+pub fn find_archive<'a>(
+    dirs: impl IntoIterator<Item = &'a Path>,
+    name: &str,
+    version: &str,
+) -> Option<PathBuf> {
+    let prefix = format!("{}-{}-", name, version);
+    dirs.into_iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                return false;
+            };
+            // What follows the version is the architecture and the extension
+            file_name.strip_prefix(&prefix).is_some_and(|rest| {
+                !rest.contains('-') && rest.contains(".pkg.tar") && !rest.ends_with(".sig")
+            })
+        })
+}
+
+/// Name and version of the package archive named `file_name`, like
+/// `name-1.0-1-x86_64.pkg.tar.zst`, or of its signature.
+pub fn parse_archive_name(file_name: &str) -> Option<(&str, &str)> {
+    let stem = &file_name[..file_name.find(".pkg.tar")?];
+    let (versioned, _arch) = stem.rsplit_once('-')?;
+    let (name, _version) = versioned.rsplit_once('-')?.0.rsplit_once('-')?;
+    Some((name, &versioned[name.len() + 1..]))
+}
+
+/// Package archives in `dirs` beyond the `keep` newest versions of each
+/// package, along with their signatures, like `paccache -rk` removes.
+pub fn stale_archives<'a>(dirs: impl IntoIterator<Item = &'a Path>, keep: usize) -> Vec<PathBuf> {
+    let archives = dirs
+        .into_iter()
+        .flat_map(cache::entries)
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_str()?;
+            let (name, version) = parse_archive_name(file_name)?;
+            Some((name.to_string(), version.to_string(), path))
+        });
+    cache::stale_versions(archives, keep, |a, b| alpm::vercmp(a.as_str(), b.as_str()))
+}
+
+/// Installs the package archive at `path`, like `pacman -U` does.
+pub fn install_file(alpm: &mut alpm::Alpm, path: &Path) -> Result<(), TransactionError> {
+    transaction(alpm, TransFlag::NONE, |alpm| {
+        let pkg = alpm.pkg_load(
+            path.as_os_str().as_bytes(),
+            true,
+            alpm.local_file_siglevel(),
+        )?;
+        alpm.trans_add_pkg(pkg).map_err(|err| err.error)
+    })
+}
+
+/// Installs the packages named `names` from the sync databases, with their
+/// dependencies, like `pacman -S` does.
+pub fn sync_packages<'a>(
+    alpm: &mut alpm::Alpm,
+    flags: TransFlag,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<(), TransactionError> {
+    transaction(alpm, flags, |alpm| {
+        let syncdbs = alpm.syncdbs();
+        for name in names {
+            let pkg = syncdbs.pkg(name)?;
+            alpm.trans_add_pkg(pkg).map_err(|err| err.error)?;
+        }
+        Ok(())
+    })
+}
+
+/// Removes the installed packages named `names`, like `pacman -R` does.
+pub fn remove_packages<'a>(
+    alpm: &mut alpm::Alpm,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<(), TransactionError> {
+    transaction(alpm, TransFlag::NONE, |alpm| {
+        let localdb = alpm.localdb();
+        for name in names {
+            alpm.trans_remove_pkg(localdb.pkg(name)?)?;
+        }
+        Ok(())
+    })
+}
+
+/// Runs a transaction with the targets `add` adds to it, resolving its
+/// dependencies and committing it like pacman does once confirmed.
+pub fn transaction(
+    alpm: &mut alpm::Alpm,
+    flags: TransFlag,
+    add: impl FnOnce(&alpm::Alpm) -> Result<(), alpm::Error>,
+) -> Result<(), TransactionError> {
+    alpm.trans_init(flags)?;
+    let result = add(alpm)
+        .map_err(TransactionError::from)
+        .and_then(|()| commit(alpm));
+    alpm.trans_release()?;
+    result
+}
+
+fn commit(alpm: &mut alpm::Alpm) -> Result<(), TransactionError> {
+    if let Err(err) = alpm.trans_prepare() {
+        return Err(TransactionError::from_prepare(&err));
+    }
+    // Nothing to do, like when every target is up to date
+    if alpm.trans_add().is_empty() && alpm.trans_remove().is_empty() {
+        return Ok(());
+    }
+    alpm.trans_commit()
+        .map_err(|err| TransactionError::from_commit(&err))
+}
+
+/// State the alpm callbacks share, so that each phase is reported once.
+struct ProgressReporter {
+    sender: Sender<ProgressEvent>,
+    phase: Option<Phase>,
+    step: usize,
+}
+impl ProgressReporter {
+    fn send(&self, event: ProgressEvent) {
+        // Callbacks cannot wait for room in the channel, a late event being
+        // worth less than a stalled transaction
+        let _ = self.sender.try_send(event);
+    }
+
+    fn phase(&mut self, phase: Phase) {
+        if self.phase != Some(phase) {
+            self.phase = Some(phase);
+            self.step = 0;
+            self.send(ProgressEvent::Phase(phase));
+        }
+    }
+}
+
+/// Reports the phases, steps, downloads and warnings of transactions to
+/// `sender`, from the event, progress, log and download callbacks of libalpm.
+pub fn set_progress_callbacks(alpm: &alpm::Alpm, sender: Sender<ProgressEvent>) {
+    let reporter = Arc::new(Mutex::new(ProgressReporter {
+        sender,
+        phase: None,
+        step: 0,
+    }));
+    alpm.set_event_cb(reporter.clone(), |event, reporter| {
+        let phase = match event.event() {
+            Event::RetrieveStart => Phase::Refreshing,
+            Event::CheckDepsStart | Event::ResolveDepsStart | Event::InterConflictsStart => {
+                Phase::Resolving
+            }
+            Event::PkgRetrieveStart(_) => Phase::Downloading,
+            Event::HookStart(_) => Phase::Hooks,
+            Event::ScriptletInfo(info) => {
+                let line = info.line().trim_end().to_string();
+                reporter.lock().unwrap().send(ProgressEvent::Log(line));
+                return;
+            }
+            _ => return,
+        };
+        reporter.lock().unwrap().phase(phase);
+    });
+    alpm.set_progress_cb(
+        reporter.clone(),
+        |progress, package, _, count, current, reporter| {
+            let mut reporter = reporter.lock().unwrap();
+            reporter.phase(match progress {
+                Progress::AddStart
+                | Progress::UpgradeStart
+                | Progress::DowngradeStart
+                | Progress::ReinstallStart => Phase::Installing,
+                Progress::RemoveStart => Phase::Removing,
+                _ => Phase::Checking,
+            });
+            if current != reporter.step {
+                reporter.step = current;
+                reporter.send(ProgressEvent::Step {
+                    step: current,
+                    total: count,
+                    package: package.into(),
+                });
+            }
+        },
+    );
+    alpm.set_log_cb(reporter.clone(), |level, message, reporter| {
+        if level.intersects(LogLevel::WARNING | LogLevel::ERROR) {
+            let warning = message.trim_end().to_string();
+            reporter
+                .lock()
+                .unwrap()
+                .send(ProgressEvent::Warning(warning));
+        }
+    });
+    alpm.set_dl_cb(reporter, |file, event, reporter| {
+        if let DownloadEvent::Progress(progress) = event.event() {
+            reporter.lock().unwrap().send(ProgressEvent::Bytes {
+                file: file.into(),
+                done: u64::try_from(progress.downloaded).unwrap_or_default(),
+                total: u64::try_from(progress.total)
+                    .ok()
+                    .filter(|total| *total > 0),
+            });
+        }
+    });
+}
+
+/// Why a transaction failed, the dependency and conflict problems libalpm
+/// found being kept apart from its other errors.
+#[derive(Debug)]
+pub enum TransactionError {
+    Alpm(alpm::Error),
+    /// Dependencies nothing satisfies, as `(package, dependency)` pairs.
+    UnsatisfiedDeps(Vec<(String, String)>),
+    /// Packages that cannot be installed together, as `(package, package, reason)`.
+    Conflicts(Vec<(String, String, String)>),
+    /// Packages that are corrupted or built for another architecture.
+    InvalidPackages(Vec<String>),
+}
+impl TransactionError {
+    fn from_prepare(err: &PrepareError) -> Self {
+        // The data is only there for these errors, and freed when dropped
+        match err.error() {
+            alpm::Error::PkgInvalidArch
+            | alpm::Error::UnsatisfiedDeps
+            | alpm::Error::ConflictingDeps => {}
+            error => return Self::Alpm(error),
+        }
+        match err.data() {
+            PrepareData::UnsatisfiedDeps(missing) => Self::UnsatisfiedDeps(
+                missing
+                    .iter()
+                    .map(|missing| (missing.target().into(), missing.depend().to_string()))
+                    .collect(),
+            ),
+            PrepareData::ConflictingDeps(conflicts) => Self::Conflicts(
+                conflicts
+                    .iter()
+                    .map(|conflict| {
+                        (
+                            conflict.package1().name().into(),
+                            conflict.package2().name().into(),
+                            conflict.reason().to_string(),
+                        )
+                    })
+                    .collect(),
+            ),
+            PrepareData::PkgInvalidArch(pkgs) => {
+                Self::InvalidPackages(pkgs.iter().map(|pkg| pkg.name().into()).collect())
+            }
+        }
+    }
+
+    fn from_commit(err: &CommitError) -> Self {
+        // File conflicts are left as a plain error: alpm 3 hands their list
+        // out as package conflicts, which it is not, so it cannot be read.
+        match err.error() {
+            alpm::Error::PkgInvalid
+            | alpm::Error::PkgInvalidSig
+            | alpm::Error::PkgInvalidChecksum => {}
+            error => return Self::Alpm(error),
+        }
+        match err.data() {
+            CommitData::PkgInvalid(pkgs) => {
+                Self::InvalidPackages(pkgs.iter().map(|pkg| pkg.to_string()).collect())
+            }
+            CommitData::FileConflict(_) => Self::Alpm(err.error()),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Alpm(err) => error_kind(err),
+            Self::UnsatisfiedDeps(_) | Self::Conflicts(_) => ErrorKind::Conflict,
+            Self::InvalidPackages(_) => ErrorKind::Other,
+        }
+    }
+}
+impl From<alpm::Error> for TransactionError {
+    fn from(value: alpm::Error) -> Self {
+        Self::Alpm(value)
+    }
+}
+impl Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Alpm(err) => write!(f, "Alpm: {}", err),
+            Self::UnsatisfiedDeps(missing) => {
+                write!(f, "Unable to satisfy dependencies:")?;
+                for (package, dependency) in missing {
+                    write!(f, "\n  {} requires {}", package, dependency)?;
+                }
+                Ok(())
+            }
+            Self::Conflicts(conflicts) => {
+                write!(f, "Conflicting packages:")?;
+                for (package1, package2, reason) in conflicts {
+                    write!(
+                        f,
+                        "\n  {} and {} are in conflict ({})",
+                        package1, package2, reason
+                    )?;
+                }
+                Ok(())
+            }
+            Self::InvalidPackages(pkgs) => write!(f, "Invalid packages: {}", pkgs.join(", ")),
+        }
+    }
+}
+impl std::error::Error for TransactionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Alpm(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub fn error_kind(error: &alpm::Error) -> ErrorKind {
+    match error {
+        alpm::Error::HandleLock => ErrorKind::Locked,
+        alpm::Error::BadPerms => ErrorKind::PermissionDenied,
+        alpm::Error::PkgNotFound | alpm::Error::DbNotFound => ErrorKind::NotFound,
+        alpm::Error::FileConflicts => ErrorKind::Conflict,
+        alpm::Error::ServerBadUrl
+        | alpm::Error::ServerNone
+        | alpm::Error::Retrieve
+        | alpm::Error::Libcurl
+        | alpm::Error::ExternalDownload => ErrorKind::Network,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Kind of a failed `pacman` invocation, guessed from its stderr.
+pub fn command_error_kind(error: &CommandError) -> ErrorKind {
+    let stderr = error.stderr();
+    if stderr
+        .iter()
+        .any(|line| line.contains("unable to lock database"))
+    {
+        ErrorKind::Locked
+    } else if stderr
+        .iter()
+        .any(|line| line.contains("unless you are root"))
+    {
+        ErrorKind::PermissionDenied
+    } else if stderr.iter().any(|line| line.contains("target not found")) {
+        ErrorKind::NotFound
+    } else {
+        error.kind()
+    }
+}
@@ -0,0 +1,81 @@
+use crate::managers::CacheUsage;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+/// Entries of a manager's caches, and those of them cleaning removes.
+#[derive(Default)]
+pub struct CacheFiles {
+    pub entries: Vec<PathBuf>,
+    pub stale: Vec<PathBuf>,
+}
+impl CacheFiles {
+    pub fn usage(&self) -> CacheUsage {
+        CacheUsage {
+            size: self.entries.iter().map(|path| disk_usage(path)).sum(),
+            reclaimable: self.stale.iter().map(|path| disk_usage(path)).sum(),
+        }
+    }
+
+    /// Removes the stale entries, returning the bytes freed.
+    pub fn clean(&self) -> std::io::Result<u64> {
+        let mut freed = 0;
+        for path in &self.stale {
+            let size = disk_usage(path);
+            let result = match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+                _ => std::fs::remove_file(path),
+            };
+            match result {
+                Ok(()) => freed += size,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(freed)
+    }
+}
+
+/// Files and directories in `dir`, none when it does not exist.
+pub fn entries(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Size in bytes of the file at `path`, or of everything under it for a directory.
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    entries(path).iter().map(|entry| disk_usage(entry)).sum()
+}
+
+/// Paths of `files` beyond the `keep` newest versions of each name, `files`
+/// giving the name and version of every path and `compare` ordering versions.
+pub fn stale_versions<V: PartialEq>(
+    files: impl IntoIterator<Item = (String, V, PathBuf)>,
+    keep: usize,
+    compare: impl Fn(&V, &V) -> Ordering,
+) -> Vec<PathBuf> {
+    let mut names = std::collections::BTreeMap::<String, Vec<(V, Vec<PathBuf>)>>::new();
+    for (name, version, path) in files {
+        let versions = names.entry(name).or_default();
+        match versions.iter_mut().find(|(other, _)| *other == version) {
+            Some((_, paths)) => paths.push(path),
+            None => versions.push((version, vec![path])),
+        }
+    }
+    names
+        .into_values()
+        .flat_map(|mut versions| {
+            versions.sort_by(|(a, _), (b, _)| compare(b, a));
+            versions.into_iter().skip(keep).flat_map(|(_, paths)| paths)
+        })
+        .collect()
+}
@@ -0,0 +1,259 @@
+use crate::managers::ErrorKind;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::io::{BufRead as _, BufReader, Read};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Number of stderr lines kept to explain a failure.
+const TAIL_LINES: usize = 10;
+
+#[derive(Debug)]
+pub enum CommandError {
+    Spawn {
+        program: String,
+        source: std::io::Error,
+    },
+    Failed {
+        program: String,
+        args: Vec<String>,
+        code: Option<i32>,
+        stderr: Vec<String>,
+    },
+}
+impl CommandError {
+    fn spawn(command: &Command, source: std::io::Error) -> Self {
+        Self::Spawn {
+            program: command.get_program().to_string_lossy().into(),
+            source,
+        }
+    }
+
+    fn failed(command: &Command, status: ExitStatus, stderr: Vec<String>) -> Self {
+        let (program, args) = describe(command);
+        Self::Failed {
+            program,
+            args,
+            code: status.code(),
+            stderr,
+        }
+    }
+
+    pub fn program(&self) -> &str {
+        match self {
+            Self::Spawn { program, .. } | Self::Failed { program, .. } => program,
+        }
+    }
+
+    /// Last lines printed on stderr by a command that ran but failed.
+    pub fn stderr(&self) -> &[String] {
+        match self {
+            Self::Spawn { .. } => &[],
+            Self::Failed { stderr, .. } => stderr,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Spawn { source, .. } => ErrorKind::of_io(source),
+            Self::Failed { .. } => ErrorKind::Command,
+        }
+    }
+
+    /// Exit code of a command that ran but failed.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Self::Spawn { .. } => None,
+            Self::Failed { code, .. } => *code,
+        }
+    }
+}
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Spawn { program, source } => write!(f, "Failed to run {}: {}", program, source),
+            Self::Failed {
+                program,
+                args,
+                code,
+                stderr,
+            } => {
+                write!(f, "\"{} {}\" ", program, args.join(" "))?;
+                match code {
+                    Some(code) => write!(f, "exited with code {}", code)?,
+                    None => write!(f, "was killed by a signal")?,
+                }
+                for line in stderr {
+                    write!(f, "\n  {}", line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Spawn { source, .. } => Some(source),
+            Self::Failed { .. } => None,
+        }
+    }
+}
+
+fn describe(command: &Command) -> (String, Vec<String>) {
+    let program = command.get_program().to_string_lossy().into();
+    let args = command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into())
+        .collect();
+    (program, args)
+}
+
+fn tail(output: &[u8]) -> Vec<String> {
+    let output = String::from_utf8_lossy(output);
+    let lines = output.lines().filter(|line| !line.trim().is_empty());
+    let mut tail = lines.map(String::from).collect::<Vec<_>>();
+    let len = tail.len();
+    tail.drain(..len.saturating_sub(TAIL_LINES));
+    tail
+}
+
+/// Runs `command` to completion, discarding its output.
+pub fn run(command: &mut Command) -> Result<(), CommandError> {
+    output(command).map(|_| ())
+}
+
+/// Runs `command` to completion and returns its stdout.
+pub fn output(command: &mut Command) -> Result<String, CommandError> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| CommandError::spawn(command, err))?;
+    if !output.status.success() {
+        return Err(CommandError::failed(
+            command,
+            output.status,
+            tail(&output.stderr),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into())
+}
+
+/// Runs `command` to completion and returns its stdout whatever its exit
+/// status, for commands that print what they would do before a prompt that
+/// the closed stdin refuses.
+pub fn output_unchecked(command: &mut Command) -> Result<String, CommandError> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| CommandError::spawn(command, err))?;
+    Ok(String::from_utf8_lossy(&output.stdout).into())
+}
+
+/// Spawns `command` and yields the lines of its stdout.
+pub fn stream_stdout(command: &mut Command) -> Result<Streaming, CommandError> {
+    Streaming::spawn(command, false)
+}
+
+/// Spawns `command` and yields the lines of its stderr.
+pub fn stream_stderr(command: &mut Command) -> Result<Streaming, CommandError> {
+    Streaming::spawn(command, true)
+}
+
+/// A running command whose output is read line by line.
+///
+/// The last lines of stderr are kept so that [`Streaming::wait`] can report
+/// them if the command fails.
+pub struct Streaming {
+    program: String,
+    args: Vec<String>,
+    child: Child,
+    lines: std::io::Lines<BufReader<Box<dyn Read + Send>>>,
+    from_stderr: bool,
+    tail: Arc<Mutex<VecDeque<String>>>,
+    stderr_reader: Option<JoinHandle<()>>,
+}
+impl Streaming {
+    fn spawn(command: &mut Command, from_stderr: bool) -> Result<Self, CommandError> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(if from_stderr {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            })
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| CommandError::spawn(command, err))?;
+
+        let tail = Arc::new(Mutex::new(VecDeque::with_capacity(TAIL_LINES)));
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let (reader, stderr_reader): (Box<dyn Read + Send>, _) = if from_stderr {
+            (Box::new(stderr), None)
+        } else {
+            let tail = tail.clone();
+            let handle = std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    push_tail(&tail, line);
+                }
+            });
+            let stdout = child.stdout.take().expect("stdout is piped");
+            (Box::new(stdout), Some(handle))
+        };
+
+        let (program, args) = describe(command);
+        Ok(Self {
+            program,
+            args,
+            child,
+            lines: BufReader::new(reader).lines(),
+            from_stderr,
+            tail,
+            stderr_reader,
+        })
+    }
+
+    /// Waits for the command to exit, failing if its exit status is not a success.
+    pub fn wait(mut self) -> Result<(), CommandError> {
+        for _ in self.by_ref() {}
+        if let Some(handle) = self.stderr_reader.take() {
+            let _ = handle.join();
+        }
+        let status = self.child.wait().map_err(|source| CommandError::Spawn {
+            program: self.program.clone(),
+            source,
+        })?;
+        if !status.success() {
+            return Err(CommandError::Failed {
+                program: self.program,
+                args: self.args,
+                code: status.code(),
+                stderr: self.tail.lock().unwrap().drain(..).collect(),
+            });
+        }
+        Ok(())
+    }
+}
+impl Iterator for Streaming {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?.ok()?;
+        if self.from_stderr {
+            push_tail(&self.tail, line.clone());
+        }
+        Some(line)
+    }
+}
+
+fn push_tail(tail: &Mutex<VecDeque<String>>, line: String) {
+    if line.trim().is_empty() {
+        return;
+    }
+    let mut tail = tail.lock().unwrap();
+    if tail.len() == TAIL_LINES {
+        tail.pop_front();
+    }
+    tail.push_back(line);
+}
@@ -0,0 +1,70 @@
+use super::dirs::DIRS;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// Content of `$XDG_CONFIG_HOME/unipac/config.toml`, empty if there is none.
+///
+/// An unreadable file is reported once on stderr and ignored.
+pub static CONFIG: Lazy<toml::Table> = Lazy::new(|| {
+    load().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        toml::Table::new()
+    })
+});
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            Self::Parse(path, err) => write!(f, "Invalid config {}: {}", path.display(), err),
+        }
+    }
+}
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read(_, err) => Some(err),
+            Self::Parse(_, err) => Some(err),
+        }
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    DIRS.find_config_file("config.toml")
+}
+
+pub fn load() -> Result<toml::Table, ConfigError> {
+    let Some(path) = path() else {
+        return Ok(toml::Table::new());
+    };
+    let content =
+        std::fs::read_to_string(&path).map_err(|err| ConfigError::Read(path.clone(), err))?;
+    content.parse().map_err(|err| ConfigError::Parse(path, err))
+}
+
+/// Deserializes the whole configuration, ignoring the keys `T` does not know.
+pub fn get<T: DeserializeOwned + Default>() -> T {
+    deserialize("configuration", toml::Value::Table(CONFIG.clone()))
+}
+
+/// Deserializes the `[name]` table of the configuration.
+pub fn section<T: DeserializeOwned + Default>(name: &str) -> T {
+    match CONFIG.get(name) {
+        Some(value) => deserialize(&format!("[{}] configuration", name), value.clone()),
+        None => T::default(),
+    }
+}
+
+fn deserialize<T: DeserializeOwned + Default>(what: &str, value: toml::Value) -> T {
+    value.try_into().unwrap_or_else(|err| {
+        eprintln!("Invalid {}: {}", what, err);
+        T::default()
+    })
+}
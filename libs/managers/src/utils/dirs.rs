@@ -6,7 +6,7 @@ pub static DIRS: Lazy<BaseDirectories> =
     Lazy::new(|| BaseDirectories::with_prefix("unipac").expect("Failed to find xdg directories"));
 
 #[cfg(feature = "aur")]
-pub static REQUESTER: Lazy<reqwest::Client> = Lazy::new(|| reqwest::Client::new());
+pub static REQUESTER: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
 
 #[cfg(feature = "aur")]
 pub fn get_aur_extracted_path<N>(name: N) -> std::io::Result<PathBuf>
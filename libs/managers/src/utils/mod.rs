@@ -1,4 +1,8 @@
-#[cfg(any(feature = "alpm"))]
+#[cfg(feature = "alpm")]
 pub mod alpm;
-#[cfg(feature = "aur")]
+pub mod cache;
+pub mod command;
+pub mod config;
 pub mod dirs;
+#[cfg(feature = "snap")]
+pub mod snapd;
@@ -0,0 +1,529 @@
+use crate::managers::{report, ErrorKind, Phase, ProgressEvent};
+use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc::Sender;
+
+/// Socket snapd serves its REST API on.
+pub const SOCKET: &str = "/run/snapd.socket";
+
+/// Delay between two looks at a running change.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A snap, as snapd describes the installed ones and the store offers the others.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Snap {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub revision: String,
+    #[serde(default)]
+    pub summary: String,
+    pub publisher: Option<Publisher>,
+    #[serde(default)]
+    pub confinement: Confinement,
+    /// `active` or `installed` for the installed revisions, the latter being
+    /// disabled, and `available` for the store ones.
+    #[serde(default)]
+    pub status: String,
+    pub tracking_channel: Option<String>,
+    /// Channel the revision comes from.
+    pub channel: Option<String>,
+    pub installed_size: Option<u64>,
+    pub install_date: Option<DateTime<Local>>,
+    pub license: Option<String>,
+    pub website: Option<String>,
+    pub store_url: Option<String>,
+    /// Channels the store offers the snap in, by name.
+    #[serde(default)]
+    pub channels: BTreeMap<String, Channel>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Publisher {
+    pub username: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub validation: Validation,
+}
+
+/// How much the store vouches for a publisher.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Validation {
+    Verified,
+    Starred,
+    #[default]
+    #[serde(other)]
+    Unproven,
+}
+impl Display for Validation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Verified => write!(f, "verified"),
+            Self::Starred => write!(f, "starred"),
+            Self::Unproven => write!(f, "unproven"),
+        }
+    }
+}
+
+/// How much of the system a snap can reach, classic and devmode snaps having
+/// to be installed with the matching flag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confinement {
+    Classic,
+    Devmode,
+    #[default]
+    #[serde(other)]
+    Strict,
+}
+impl Display for Confinement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Classic => write!(f, "classic"),
+            Self::Devmode => write!(f, "devmode"),
+            Self::Strict => write!(f, "strict"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Channel {
+    pub version: String,
+    pub revision: String,
+    #[serde(default)]
+    pub confinement: Confinement,
+}
+
+/// A change snapd makes to the system, made of tasks run one after the other.
+#[derive(Debug, Deserialize)]
+pub struct Change {
+    pub summary: String,
+    /// `Do`, `Doing`, `Done`, `Error`, `Undone`...
+    pub status: String,
+    pub ready: bool,
+    pub err: Option<String>,
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Task {
+    pub kind: String,
+    pub summary: String,
+    pub status: String,
+    pub progress: TaskProgress,
+}
+
+/// Progress of a task, counted in bytes by downloads and out of 1 otherwise.
+#[derive(Debug, Deserialize)]
+pub struct TaskProgress {
+    pub done: u64,
+    pub total: u64,
+}
+
+/// Body of every snapd response.
+#[derive(Deserialize)]
+struct Response {
+    /// `sync`, `async` or `error`.
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(rename = "status-code")]
+    status_code: u16,
+    /// Change started by an `async` response.
+    change: Option<String>,
+    #[serde(default)]
+    result: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+    kind: Option<String>,
+}
+
+/// Client of the REST API snapd serves on its unix socket.
+///
+/// Requests are sent as HTTP/1.0, for snapd to answer with a plain body and
+/// close the connection, so that any server listening on a socket at
+/// `socket` can stand in for it.
+pub struct Client {
+    socket: PathBuf,
+}
+impl Client {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// Result of a synchronous request.
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, SnapdError> {
+        let response = self.request("GET", path, None).await?;
+        serde_json::from_value(response.result).map_err(SnapdError::Json)
+    }
+
+    /// Like [`Client::get`], `None` standing for the snaps snapd does not know.
+    pub async fn get_optional<T: DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<Option<T>, SnapdError> {
+        match self.get(path).await {
+            Ok(result) => Ok(Some(result)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Sends an action, returning the change it started unless snapd
+    /// handled it right away.
+    pub async fn post(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<Option<String>, SnapdError> {
+        Ok(self.request("POST", path, Some(body)).await?.change)
+    }
+
+    /// Waits for the change `id` to be ready, reporting the phases, downloads
+    /// and tasks it goes through to `sender`.
+    pub async fn wait(
+        &self,
+        id: &str,
+        sender: &Option<Sender<ProgressEvent>>,
+    ) -> Result<(), SnapdError> {
+        let path = format!("/v2/changes/{}", id);
+        let mut phase = None;
+        let mut summary = String::new();
+        loop {
+            let change: Change = self.get(&path).await?;
+            if let Some(task) = change.tasks.iter().find(|task| task.status == "Doing") {
+                if let Some(task_phase) = task_phase(&task.kind).filter(|&p| phase != Some(p)) {
+                    phase = Some(task_phase);
+                    report(sender, ProgressEvent::Phase(task_phase)).await;
+                }
+                if task.progress.total > 1 {
+                    let event = ProgressEvent::Bytes {
+                        file: task.summary.clone(),
+                        done: task.progress.done,
+                        total: Some(task.progress.total),
+                    };
+                    report(sender, event).await;
+                } else if task.summary != summary {
+                    summary = task.summary.clone();
+                    report(sender, ProgressEvent::Log(summary.clone())).await;
+                }
+            }
+            if change.ready {
+                return match change.status.as_str() {
+                    "Done" => Ok(()),
+                    _ => Err(SnapdError::Change {
+                        message: change.err.unwrap_or(change.status),
+                        summary: change.summary,
+                    }),
+                };
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<Response, SnapdError> {
+        let body = match body {
+            Some(body) => serde_json::to_vec(body).map_err(SnapdError::Json)?,
+            None => Vec::new(),
+        };
+        let head = format!(
+            "{} {} HTTP/1.0\r\n\
+             Host: localhost\r\n\
+             X-Allow-Interaction: true\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n",
+            method,
+            path,
+            body.len()
+        );
+
+        let mut stream = UnixStream::connect(&self.socket)
+            .await
+            .map_err(SnapdError::Io)?;
+        stream
+            .write_all(&[head.as_bytes(), &body].concat())
+            .await
+            .map_err(SnapdError::Io)?;
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .await
+            .map_err(SnapdError::Io)?;
+
+        let body_start = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| SnapdError::Protocol("response without a body".into()))?;
+        let response: Response =
+            serde_json::from_slice(&response[body_start + 4..]).map_err(SnapdError::Json)?;
+        if response.kind == "error" {
+            let error: ApiError =
+                serde_json::from_value(response.result).map_err(SnapdError::Json)?;
+            return Err(SnapdError::Api {
+                status: response.status_code,
+                kind: error.kind,
+                message: error.message,
+            });
+        }
+        Ok(response)
+    }
+}
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(SOCKET)
+    }
+}
+
+/// Percent-encodes `value` for a path segment or a query parameter.
+pub fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn task_phase(kind: &str) -> Option<Phase> {
+    let phase = match kind {
+        "prerequisites" => Phase::Resolving,
+        "download-snap" => Phase::Downloading,
+        "validate-snap" => Phase::Checking,
+        "mount-snap"
+        | "copy-snap-data"
+        | "setup-profiles"
+        | "link-snap"
+        | "auto-connect"
+        | "setup-aliases"
+        | "start-snap-services" => Phase::Installing,
+        "stop-snap-services" | "remove-aliases" | "unlink-snap" | "remove-profiles"
+        | "clear-snap" | "discard-snap" => Phase::Removing,
+        "run-hook" => Phase::Hooks,
+        _ => return None,
+    };
+    Some(phase)
+}
+
+#[derive(Debug)]
+pub enum SnapdError {
+    /// The socket could not be reached, or the connection broke.
+    Io(std::io::Error),
+    Protocol(String),
+    Json(serde_json::Error),
+    /// snapd refused the request.
+    Api {
+        status: u16,
+        kind: Option<String>,
+        message: String,
+    },
+    /// A change failed and was undone.
+    Change {
+        summary: String,
+        message: String,
+    },
+}
+impl SnapdError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Io(err) => ErrorKind::of_io(err),
+            Self::Protocol(_) | Self::Json(_) => ErrorKind::Parse,
+            Self::Api { status, kind, .. } => match kind.as_deref() {
+                Some(
+                    "snap-not-found"
+                    | "snap-not-installed"
+                    | "snap-channel-not-available"
+                    | "snap-revision-not-available",
+                ) => ErrorKind::NotFound,
+                Some("snap-change-conflict") => ErrorKind::Locked,
+                Some("network-timeout" | "dns-failure") => ErrorKind::Network,
+                _ if matches!(status, 401 | 403) => ErrorKind::PermissionDenied,
+                _ if *status == 404 => ErrorKind::NotFound,
+                _ => ErrorKind::Other,
+            },
+            Self::Change { .. } => ErrorKind::Other,
+        }
+    }
+}
+impl Display for SnapdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "Cannot talk to snapd: {}", err),
+            Self::Protocol(err) => write!(f, "Invalid response from snapd: {}", err),
+            Self::Json(err) => write!(f, "Invalid response from snapd: {}", err),
+            Self::Api { message, .. } => write!(f, "snapd: {}", message),
+            Self::Change { summary, message } => write!(f, "{}: {}", summary, message),
+        }
+    }
+}
+impl std::error::Error for SnapdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Json(err) => Some(err),
+            Self::Protocol(_) | Self::Api { .. } | Self::Change { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use tokio::net::UnixListener;
+
+    fn sync(result: Value) -> (&'static str, Value) {
+        let body = json!({ "type": "sync", "status-code": 200, "status": "OK", "result": result });
+        ("200 OK", body)
+    }
+
+    /// What a snapd knowing one installed snap and one store snap answers.
+    fn respond(path: &str) -> (&'static str, Value) {
+        match path {
+            "/v2/snaps" => sync(json!([{
+                "name": "hello",
+                "version": "2.10",
+                "revision": "42",
+                "summary": "GNU Hello",
+                "publisher": {
+                    "id": "canonical",
+                    "username": "canonical",
+                    "display-name": "Canonical",
+                    "validation": "verified"
+                },
+                "confinement": "strict",
+                "status": "active",
+                "tracking-channel": "latest/stable",
+                "channel": "stable",
+                "installed-size": 98304
+            }])),
+            "/v2/find?name=code" => sync(json!([{
+                "name": "code",
+                "version": "1.90",
+                "revision": "160",
+                "summary": "Code editing. Redefined.",
+                "publisher": {
+                    "id": "vscode",
+                    "username": "vscode",
+                    "display-name": "Visual Studio Code",
+                    "validation": "unknown"
+                },
+                "confinement": "classic",
+                "status": "available",
+                "channels": {
+                    "latest/stable": {
+                        "version": "1.90",
+                        "revision": "160",
+                        "confinement": "classic"
+                    }
+                }
+            }])),
+            "/v2/changes/1" => sync(json!({
+                "summary": "Install \"code\" snap",
+                "status": "Done",
+                "ready": true,
+                "tasks": [{
+                    "kind": "download-snap",
+                    "summary": "Download snap \"code\"",
+                    "status": "Done",
+                    "progress": { "done": 1024, "total": 1024 }
+                }]
+            })),
+            "/v2/changes/2" => sync(json!({
+                "summary": "Remove \"hello\" snap",
+                "status": "Error",
+                "ready": true,
+                "err": "cannot remove \"hello\"",
+                "tasks": []
+            })),
+            _ => (
+                "404 Not Found",
+                json!({
+                    "type": "error",
+                    "status-code": 404,
+                    "status": "Not Found",
+                    "result": { "message": "snap not installed", "kind": "snap-not-installed" }
+                }),
+            ),
+        }
+    }
+
+    async fn serve(listener: UnixListener) {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            let (status, body) = respond(path);
+            let response = format!(
+                "HTTP/1.0 {}\r\nContent-Type: application/json\r\n\r\n{}",
+                status, body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn client_talks_to_a_fake_snapd() {
+        let root = tempfile::tempdir().unwrap();
+        let socket = root.path().join("snapd.socket");
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(serve(listener));
+        let client = Client::new(&socket);
+
+        let installed: Vec<Snap> = client.get("/v2/snaps").await.unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].name, "hello");
+        assert_eq!(installed[0].revision, "42");
+        assert_eq!(installed[0].confinement, Confinement::Strict);
+        assert_eq!(installed[0].installed_size, Some(98304));
+        let publisher = installed[0].publisher.as_ref().unwrap();
+        assert_eq!(publisher.validation, Validation::Verified);
+
+        let found: Vec<Snap> = client.get("/v2/find?name=code").await.unwrap();
+        assert_eq!(found[0].confinement, Confinement::Classic);
+        assert_eq!(found[0].channels["latest/stable"].revision, "160");
+        let publisher = found[0].publisher.as_ref().unwrap();
+        assert_eq!(publisher.validation, Validation::Unproven);
+
+        let missing: Option<Snap> = client.get_optional("/v2/snaps/missing").await.unwrap();
+        assert!(missing.is_none());
+
+        client.wait("1", &None).await.unwrap();
+        let err = client.wait("2", &None).await.unwrap_err();
+        assert!(matches!(err, SnapdError::Change { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Remove \"hello\" snap: cannot remove \"hello\""
+        );
+    }
+}